@@ -0,0 +1,218 @@
+use crate::search_engine::SearchEngine;
+use crate::{SearchFilter, SearchResult};
+use anyhow::{bail, Context, Result};
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+/// Returns the 1-based line of the first occurrence of `query` in the file
+/// at `path`, or `None` when it cannot be read or does not contain it.
+pub fn first_match_line(path: &Path, query: &str, case_sensitive: bool) -> Option<usize> {
+    let content = crate::content::decode(&std::fs::read(path).ok()?, None)?;
+    let (start, _end) = *SearchEngine::new()
+        .find_matches(&content, query, case_sensitive)
+        .first()?;
+    Some(content[..start].matches('\n').count() + 1)
+}
+
+/// The user's editor command from `$VISUAL` or `$EDITOR`, falling back to `vi`.
+pub fn editor_command() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Opens `path` in the user's editor, jumping to `line` when given, and
+/// waits for the editor to exit.
+///
+/// The line is passed as `+N`, which vi, vim, nano, emacs and most
+/// terminal editors understand.
+pub fn open_in_editor(path: &Path, line: Option<usize>) -> Result<()> {
+    let editor = editor_command();
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let mut command = Command::new(program);
+    command.args(parts);
+    if let Some(line) = line {
+        command.arg(format!("+{}", line));
+    }
+    command.arg(path);
+
+    let status = command
+        .status()
+        .with_context(|| format!("failed to launch editor `{}`", editor))?;
+    if !status.success() {
+        bail!("editor `{}` exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// Opens `path` with the desktop's default application.
+pub fn open_with_system(path: &Path) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut command = Command::new("xdg-open");
+
+    let status = command
+        .arg(path)
+        .status()
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    if !status.success() {
+        bail!("opening {} failed with {}", path.display(), status);
+    }
+    Ok(())
+}
+
+/// Opens a search hit: content matches go to the editor at the first
+/// matching line, everything else to the default application.
+pub fn open_result(result: &SearchResult, filter: &SearchFilter) -> Result<()> {
+    let path = crate::archive::on_disk(&result.os_path());
+    if filter.search_content && result.matched_content.is_some() {
+        let line = first_match_line(&path, &filter.query, filter.case_sensitive);
+        open_in_editor(&path, line)
+    } else {
+        open_with_system(&path)
+    }
+}
+
+/// Runs `template` through the shell for `path`, like `find -exec`.
+///
+/// Every `{}` is replaced by the quoted path; without a placeholder the
+/// path is appended as the last argument.
+pub fn exec_template(template: &str, path: &Path) -> Result<ExitStatus> {
+    let command_line = expand_template(template, path);
+
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+
+    command
+        .arg(&command_line)
+        .status()
+        .with_context(|| format!("failed to run `{}`", command_line.to_string_lossy()))
+}
+
+/// Builds the command line as an `OsString` so paths that are not valid
+/// UTF-8 reach the shell byte for byte.
+fn expand_template(template: &str, path: &Path) -> OsString {
+    let quoted = shell_quote(path.as_os_str());
+    let mut command_line = OsString::new();
+    if template.contains("{}") {
+        let mut parts = template.split("{}");
+        command_line.push(parts.next().unwrap_or_default());
+        for part in parts {
+            command_line.push(&quoted);
+            command_line.push(part);
+        }
+    } else {
+        command_line.push(template);
+        command_line.push(" ");
+        command_line.push(&quoted);
+    }
+    command_line
+}
+
+#[cfg(unix)]
+fn shell_quote(value: &OsStr) -> OsString {
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    let mut quoted = vec![b'\''];
+    for &byte in value.as_bytes() {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+    OsString::from_vec(quoted)
+}
+
+#[cfg(not(unix))]
+fn shell_quote(value: &OsStr) -> OsString {
+    format!("\"{}\"", value.to_string_lossy()).into()
+}
+
+/// Copies `text` to the clipboard with the OSC 52 terminal escape sequence,
+/// which works over SSH and needs no clipboard tool on the host.
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut out = std::io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64_encode(text.as_bytes()))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"/tmp/a.rs"), "L3RtcC9hLnJz");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_template() {
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(expand_template("wc -l {}", Path::new("/tmp/a b.rs")), "wc -l '/tmp/a b.rs'");
+        assert_eq!(expand_template("ls -l", Path::new("/x")), "ls -l '/x'");
+        assert_eq!(expand_template("cp {} {}.bak", Path::new("/it's")), "cp '/it'\\''s' '/it'\\''s'.bak");
+
+        let raw = Path::new(OsStr::from_bytes(b"/tmp/bad\xffname"));
+        assert_eq!(expand_template("cat {}", raw).as_bytes(), b"cat '/tmp/bad\xffname'");
+    }
+
+    #[test]
+    fn test_first_match_line() {
+        let path = std::env::temp_dir().join(format!("rust-search-line-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\ntwo\nthree Needle\nneedle").unwrap();
+
+        assert_eq!(first_match_line(&path, "needle", false), Some(3));
+        assert_eq!(first_match_line(&path, "needle", true), Some(4));
+        assert_eq!(first_match_line(&path, "missing", false), None);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
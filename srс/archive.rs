@@ -0,0 +1,187 @@
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Joins an archive path and a member name in virtual paths such as
+/// `bundle.zip!/src/lib.rs`.
+pub const SEPARATOR: &str = "!/";
+
+/// Archive formats whose members can be indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+/// Recognises an archive by its file name.
+pub fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let has = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+    if has(&[".zip", ".jar", ".war", ".ear"]) {
+        Some(ArchiveKind::Zip)
+    } else if has(&[".tar"]) {
+        Some(ArchiveKind::Tar)
+    } else if has(&[".tar.gz", ".tgz"]) {
+        Some(ArchiveKind::TarGz)
+    } else if has(&[".tar.zst", ".tzst"]) {
+        Some(ArchiveKind::TarZst)
+    } else {
+        None
+    }
+}
+
+/// For a single compressed file such as `app.log.gz`, the name it had before
+/// compression. Compressed tarballs are archives, not compressed files.
+pub fn decompressed_name(path: &Path) -> Option<&Path> {
+    let name = path.file_name()?.to_str()?;
+    if archive_kind(path).is_some() {
+        return None;
+    }
+    let lower = name.to_lowercase();
+    [".gz", ".zst"]
+        .iter()
+        .find(|suffix| lower.ends_with(*suffix))
+        .map(|suffix| Path::new(&name[..name.len() - suffix.len()]))
+}
+
+/// Streams the decompressed content of a `.gz` or `.zst` file.
+pub fn decompress(path: &Path) -> std::io::Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(path)?);
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zst")) {
+        Ok(Box::new(zstd::Decoder::with_buffer(file)?))
+    } else {
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(file)))
+    }
+}
+
+/// Calls `visit` with the name, size and content of every regular file in
+/// the archive at `path`, in archive order.
+///
+/// Members that cannot be read, such as encrypted zip entries, are skipped.
+pub fn for_each_member(
+    path: &Path,
+    kind: ArchiveKind,
+    mut visit: impl FnMut(&str, u64, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let file = BufReader::new(File::open(path)?);
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let Ok(mut member) = archive.by_index(i) else { continue };
+                if member.is_file() {
+                    let name = member.name().to_string();
+                    let size = member.size();
+                    visit(&name, size, &mut member)?;
+                }
+            }
+        }
+        ArchiveKind::Tar => visit_tar(file, visit)?,
+        ArchiveKind::TarGz => visit_tar(flate2::read::MultiGzDecoder::new(file), visit)?,
+        ArchiveKind::TarZst => visit_tar(zstd::Decoder::with_buffer(file)?, visit)?,
+    }
+    Ok(())
+}
+
+fn visit_tar<R: Read>(reader: R, mut visit: impl FnMut(&str, u64, &mut dyn Read) -> Result<()>) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for member in archive.entries()? {
+        let mut member = member?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        let name = member.path()?.to_string_lossy().into_owned();
+        let size = member.size();
+        visit(&name, size, &mut member)?;
+    }
+    Ok(())
+}
+
+/// The virtual path of `member` inside the archive at `archive`.
+pub fn member_path(archive: &Path, member: &str) -> PathBuf {
+    let mut path = OsString::from(archive.as_os_str());
+    path.push(SEPARATOR);
+    path.push(member.trim_start_matches('/'));
+    PathBuf::from(path)
+}
+
+/// The archive holding the virtual path `path`, or `None` for a path that is
+/// not inside an archive.
+pub fn containing_archive(path: &Path) -> Option<PathBuf> {
+    let bytes = crate::models::path_bytes(path);
+    let separator = SEPARATOR.as_bytes();
+    bytes
+        .windows(separator.len())
+        .enumerate()
+        .filter(|(_, window)| *window == separator)
+        .map(|(i, _)| crate::models::path_from_bytes(&bytes[..i]))
+        .find(|archive| archive_kind(archive).is_some() && archive.is_file())
+}
+
+/// Splits the virtual path of an archive member into the archive and the
+/// member name, or `None` for a path that is not inside an archive.
+pub fn split_member(path: &Path) -> Option<(PathBuf, String)> {
+    let archive = containing_archive(path)?;
+    let bytes = crate::models::path_bytes(path);
+    let name = &bytes[crate::models::path_bytes(&archive).len() + SEPARATOR.len()..];
+    Some((archive, String::from_utf8_lossy(name).into_owned()))
+}
+
+/// Reads the member `name` of the archive at `path` whole, or `None` when
+/// the archive has no such member. Fails for members over `limit` bytes.
+pub fn read_member(path: &Path, name: &str, limit: u64) -> Result<Option<Vec<u8>>> {
+    let Some(kind) = archive_kind(path) else {
+        anyhow::bail!("{} is not an archive", path.display());
+    };
+    let mut found = None;
+    for_each_member(path, kind, |member, size, reader| {
+        if found.is_some() || member.trim_start_matches('/') != name {
+            return Ok(());
+        }
+        if size > limit {
+            anyhow::bail!("{} is {} bytes, more than the {} allowed", name, size, limit);
+        }
+        let mut bytes = Vec::with_capacity(size as usize);
+        reader.take(limit).read_to_end(&mut bytes)?;
+        found = Some(bytes);
+        Ok(())
+    })?;
+    Ok(found)
+}
+
+/// The file on disk behind `path`: the archive for a member, otherwise the
+/// path itself.
+pub fn on_disk(path: &Path) -> PathBuf {
+    if path.exists() {
+        return path.to_path_buf();
+    }
+    containing_archive(path).unwrap_or_else(|| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_names() {
+        assert_eq!(archive_kind(Path::new("a/bundle.ZIP")), Some(ArchiveKind::Zip));
+        assert_eq!(archive_kind(Path::new("lib.jar")), Some(ArchiveKind::Zip));
+        assert_eq!(archive_kind(Path::new("src.tar.gz")), Some(ArchiveKind::TarGz));
+        assert_eq!(archive_kind(Path::new("src.tzst")), Some(ArchiveKind::TarZst));
+        assert_eq!(archive_kind(Path::new("app.log.gz")), None);
+
+        assert_eq!(decompressed_name(Path::new("/var/log/app.log.gz")), Some(Path::new("app.log")));
+        assert_eq!(decompressed_name(Path::new("trace.json.zst")), Some(Path::new("trace.json")));
+        assert_eq!(decompressed_name(Path::new("src.tar.gz")), None);
+        assert_eq!(decompressed_name(Path::new("notes.txt")), None);
+
+        assert_eq!(
+            member_path(Path::new("/x/bundle.zip"), "src/lib.rs"),
+            PathBuf::from("/x/bundle.zip!/src/lib.rs")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::web_server::api_error;
+
+/// Environment variable holding comma-separated read-only API keys.
+pub const READ_KEYS_ENV: &str = "RUST_SEARCH_API_KEYS";
+/// Environment variable holding comma-separated admin API keys.
+pub const ADMIN_KEYS_ENV: &str = "RUST_SEARCH_ADMIN_KEYS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// May search, read stats and fetch indexed files.
+    ReadOnly,
+    /// May additionally start indexing, vacuum and clear the database.
+    Admin,
+}
+
+impl Role {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "read" | "readonly" | "read-only" => Some(Role::ReadOnly),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// The set of API keys accepted by the server.
+///
+/// An empty set disables authentication entirely.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: Vec<(String, Role)>,
+}
+
+impl ApiKeys {
+    /// Loads keys from an optional key file plus the environment.
+    ///
+    /// The key file holds one `<role> <key>` pair per line, where role is
+    /// `read` or `admin`; blank lines and `#` comments are ignored.
+    pub fn load(file: Option<&Path>) -> Result<Self> {
+        let mut keys = match file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read key file {}", path.display()))?;
+                Self::parse(&text)?
+            }
+            None => Self::default(),
+        };
+
+        for (var, role) in [(READ_KEYS_ENV, Role::ReadOnly), (ADMIN_KEYS_ENV, Role::Admin)] {
+            if let Ok(value) = std::env::var(var) {
+                for key in value.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                    keys.insert(key, role);
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut keys = Self::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (role, key) = match line.split_once(char::is_whitespace) {
+                Some((role, key)) if !key.trim().is_empty() => (role, key.trim()),
+                _ => bail!("line {}: expected `<role> <key>`", idx + 1),
+            };
+            let role = match Role::parse(role) {
+                Some(role) => role,
+                None => bail!("line {}: unknown role `{}`", idx + 1, role),
+            };
+            keys.insert(key, role);
+        }
+        Ok(keys)
+    }
+
+    fn insert(&mut self, key: &str, role: Role) {
+        match self.keys.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = entry.1.max(role),
+            None => self.keys.push((key.to_string(), role)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Returns the role granted to `token`, comparing against every key in
+    /// constant time so response timing does not leak key prefixes.
+    pub fn role_for(&self, token: &str) -> Option<Role> {
+        let mut granted = None;
+        for (key, role) in &self.keys {
+            if constant_time_eq(key.as_bytes(), token.as_bytes()) {
+                granted = Some(*role);
+            }
+        }
+        granted
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// State for [`require_role`]: the accepted keys and the minimum role.
+#[derive(Clone)]
+pub struct AuthLayerState {
+    pub keys: Arc<ApiKeys>,
+    pub required: Role,
+}
+
+/// Extracts the API key from `Authorization: Bearer`, `X-API-Key`, or an
+/// `api_key` query parameter (for WebSocket clients that cannot set headers).
+fn request_token(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(value) = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Some(token.trim().to_string());
+        }
+    }
+    if let Some(value) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        return Some(value.trim().to_string());
+    }
+
+    let Query(params) = Query::<HashMap<String, String>>::try_from_uri(request.uri()).ok()?;
+    params.get("api_key").cloned()
+}
+
+/// Middleware rejecting requests whose key does not grant the required role.
+pub async fn require_role(
+    State(auth): State<AuthLayerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !auth.keys.is_enabled() {
+        return next.run(request).await;
+    }
+
+    match request_token(&request).and_then(|token| auth.keys.role_for(&token)) {
+        Some(role) if role >= auth.required => next.run(request).await,
+        Some(_) => api_error(StatusCode::FORBIDDEN, "this API key is read-only"),
+        None => api_error(StatusCode::UNAUTHORIZED, "missing or invalid API key"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_file() {
+        let keys = ApiKeys::parse(
+            "# team keys\nread viewer-key\n\nadmin ops-key\nreadonly viewer-key\n",
+        )
+        .unwrap();
+
+        assert!(keys.is_enabled());
+        assert_eq!(keys.role_for("viewer-key"), Some(Role::ReadOnly));
+        assert_eq!(keys.role_for("ops-key"), Some(Role::Admin));
+        assert_eq!(keys.role_for("ops-ke"), None);
+        assert_eq!(keys.role_for(""), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_lines() {
+        assert!(ApiKeys::parse("admin").is_err());
+        assert!(ApiKeys::parse("owner some-key").is_err());
+        assert!(!ApiKeys::parse("").unwrap().is_enabled());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::IndexSettings;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Overrides the config file location.
+pub const CONFIG_ENV: &str = "RUST_SEARCH_CONFIG";
+/// Overrides the database path.
+pub const DB_ENV: &str = "RUST_SEARCH_DB";
+/// Overrides the roots, separated like `PATH`.
+pub const ROOTS_ENV: &str = "RUST_SEARCH_ROOTS";
+/// Overrides the excludes, comma separated.
+pub const EXCLUDES_ENV: &str = "RUST_SEARCH_EXCLUDES";
+/// Overrides the text extensions, comma separated.
+pub const TEXT_EXTENSIONS_ENV: &str = "RUST_SEARCH_TEXT_EXTENSIONS";
+/// Overrides the binary extensions, comma separated.
+pub const BINARY_EXTENSIONS_ENV: &str = "RUST_SEARCH_BINARY_EXTENSIONS";
+/// Overrides the fallback encoding for 8-bit text.
+pub const ENCODING_ENV: &str = "RUST_SEARCH_ENCODING";
+/// Overrides the content size limit per file, in bytes.
+pub const MAX_CONTENT_SIZE_ENV: &str = "RUST_SEARCH_MAX_CONTENT_SIZE";
+/// Turns indexing of archive members on (`1`, `true`) or off.
+pub const ARCHIVES_ENV: &str = "RUST_SEARCH_ARCHIVES";
+/// Turns partial content hashing during indexing on (`1`, `true`) or off.
+pub const HASH_ENV: &str = "RUST_SEARCH_HASH";
+/// Overrides the default result limit.
+pub const LIMIT_ENV: &str = "RUST_SEARCH_LIMIT";
+/// Overrides the server bind address.
+pub const HOST_ENV: &str = "RUST_SEARCH_HOST";
+/// Overrides the server port.
+pub const PORT_ENV: &str = "RUST_SEARCH_PORT";
+
+/// Defaults shared by every subcommand.
+///
+/// Values are layered: built-in defaults, then the TOML config file, then
+/// `RUST_SEARCH_*` environment variables. Command-line flags win over all
+/// of them and are applied by the CLI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Database path; `index.db` under the XDG data directory when unset.
+    pub db: Option<PathBuf>,
+    /// Directories indexed by `rust-search index` without a path.
+    pub roots: Vec<PathBuf>,
+    pub excludes: Vec<String>,
+    pub text_extensions: Vec<String>,
+    pub binary_extensions: Vec<String>,
+    /// Encoding of 8-bit text that is not UTF-8, e.g. `windows-1251`.
+    pub fallback_encoding: Option<String>,
+    /// Bytes of content indexed per file; larger files are truncated.
+    pub max_content_size: Option<u64>,
+    /// Index the members of zip and tar archives as `archive!/member`.
+    pub archives: bool,
+    /// Store a partial content hash per file to speed up `dupes`.
+    pub hash_content: bool,
+    pub search: SearchConfig,
+    pub server: ServerSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub limit: usize,
+    /// Largest file size returned, in bytes; unlimited when unset.
+    pub max_size: Option<u64>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self { limit: 100, max_size: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    pub auth_file: Option<PathBuf>,
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            auth_file: None,
+            cors_origins: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// `rust-search/config.toml` under the XDG config directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust-search").join("config.toml"))
+    }
+
+    /// The config file to read: `explicit`, then `RUST_SEARCH_CONFIG`, then
+    /// the default location.
+    pub fn path(explicit: Option<&Path>) -> Option<PathBuf> {
+        explicit
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+            .or_else(Self::default_path)
+    }
+
+    /// Loads the config file and applies environment overrides.
+    ///
+    /// A missing file at the default location is not an error; a missing
+    /// file that was asked for explicitly is.
+    pub fn load(explicit: Option<&Path>) -> Result<Self> {
+        let required = explicit.is_some() || std::env::var_os(CONFIG_ENV).is_some();
+        let mut config = match Self::path(explicit) {
+            Some(path) if required || path.exists() => {
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read config {}", path.display()))?;
+                Self::parse(&text).with_context(|| format!("invalid config {}", path.display()))?
+            }
+            _ => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config: Self = toml::from_str(text)?;
+        config.db = config.db.map(|db| expand_home(&db));
+        config.roots = config.roots.iter().map(|root| expand_home(root)).collect();
+        config.server.auth_file = config.server.auth_file.map(|file| expand_home(&file));
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(db) = var(DB_ENV) {
+            self.db = Some(expand_home(Path::new(&db)));
+        }
+        if let Some(roots) = var(ROOTS_ENV) {
+            self.roots = std::env::split_paths(&roots).map(|root| expand_home(&root)).collect();
+        }
+        if let Some(excludes) = var(EXCLUDES_ENV) {
+            self.excludes = split_list(&excludes);
+        }
+        if let Some(extensions) = var(TEXT_EXTENSIONS_ENV) {
+            self.text_extensions = split_list(&extensions);
+        }
+        if let Some(extensions) = var(BINARY_EXTENSIONS_ENV) {
+            self.binary_extensions = split_list(&extensions);
+        }
+        if let Some(encoding) = var(ENCODING_ENV) {
+            self.fallback_encoding = Some(encoding);
+        }
+        if let Some(size) = var(MAX_CONTENT_SIZE_ENV) {
+            self.max_content_size =
+                Some(size.parse().with_context(|| format!("invalid {}", MAX_CONTENT_SIZE_ENV))?);
+        }
+        if let Some(archives) = var(ARCHIVES_ENV) {
+            self.archives = parse_flag(ARCHIVES_ENV, &archives)?;
+        }
+        if let Some(hash) = var(HASH_ENV) {
+            self.hash_content = parse_flag(HASH_ENV, &hash)?;
+        }
+        if let Some(limit) = var(LIMIT_ENV) {
+            self.search.limit = limit.parse().with_context(|| format!("invalid {}", LIMIT_ENV))?;
+        }
+        if let Some(host) = var(HOST_ENV) {
+            self.server.host = host;
+        }
+        if let Some(port) = var(PORT_ENV) {
+            self.server.port = port.parse().with_context(|| format!("invalid {}", PORT_ENV))?;
+        }
+        Ok(())
+    }
+
+    /// The configured database, or the default under the XDG data directory.
+    pub fn db_path(&self) -> PathBuf {
+        self.db.clone().unwrap_or_else(default_db_path)
+    }
+
+    pub fn index_settings(&self) -> IndexSettings {
+        IndexSettings {
+            excludes: self.excludes.clone(),
+            text_extensions: self.text_extensions.clone(),
+            binary_extensions: self.binary_extensions.clone(),
+            fallback_encoding: self.fallback_encoding.clone(),
+            max_content_size: self.max_content_size,
+            archives: self.archives,
+            hash_content: self.hash_content,
+        }
+    }
+}
+
+/// `rust-search/index.db` under the XDG data directory, falling back to
+/// `results.db` in the working directory when there is no home.
+pub fn default_db_path() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join("rust-search").join("index.db"))
+        .unwrap_or_else(|| PathBuf::from("results.db"))
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Reads an on/off environment variable.
+fn parse_flag(name: &str, value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => anyhow::bail!("invalid {}: expected true or false", name),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            db = "/var/lib/rust-search/index.db"
+            roots = ["/srv/code"]
+            excludes = [".git", "*.log"]
+
+            [search]
+            limit = 20
+
+            [server]
+            port = 8080
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.db_path(), PathBuf::from("/var/lib/rust-search/index.db"));
+        assert_eq!(config.roots, vec![PathBuf::from("/srv/code")]);
+        assert_eq!(config.index_settings().excludes, vec![".git", "*.log"]);
+        assert_eq!(config.search.limit, 20);
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.server.host, "127.0.0.1");
+
+        assert!(Config::parse("databse = \"typo.db\"").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::parse("db = \"/from/file.db\"\n[server]\nport = 8080").unwrap();
+        let env: HashMap<&str, &str> = HashMap::from([
+            (DB_ENV, "/from/env.db"),
+            (EXCLUDES_ENV, "target, node_modules,"),
+            (PORT_ENV, "9000"),
+        ]);
+        config.apply_env(|name| env.get(name).map(|v| v.to_string())).unwrap();
+
+        assert_eq!(config.db_path(), PathBuf::from("/from/env.db"));
+        assert_eq!(config.excludes, vec!["target", "node_modules"]);
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.search.limit, 100);
+
+        let bad = HashMap::from([(LIMIT_ENV, "many")]);
+        assert!(config.apply_env(|name| bad.get(name).map(|v| v.to_string())).is_err());
+    }
+}
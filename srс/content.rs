@@ -0,0 +1,251 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use std::io::Read;
+
+/// How many leading bytes are inspected to tell text from binary.
+pub const SNIFF_LEN: usize = 8192;
+
+/// How many bytes are read and decoded at a time.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Content stored per file when no limit is configured.
+pub const DEFAULT_MAX_CONTENT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Extensions that are read as text without sniffing.
+pub const TEXT_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "ts", "go", "c", "cpp", "h", "hpp", "java", "kt", "swift", "rb", "php",
+    "scala", "sh", "bash", "yml", "yaml", "json", "toml", "xml", "html", "css", "sql", "md",
+    "txt", "log", "ini", "cfg", "conf", "csv", "tsv", "tex", "rst", "mk", "cmake",
+];
+
+/// Extensions that are never opened for content.
+pub const BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "mp3", "mp4", "mkv", "avi", "mov", "flac",
+    "ogg", "wav", "zip", "gz", "xz", "bz2", "zst", "7z", "rar", "tar", "iso", "exe", "dll", "so",
+    "dylib", "o", "a", "class", "jar", "wasm", "pyc", "db", "sqlite",
+];
+
+/// Decides from its first bytes whether a file holds text.
+///
+/// Scripts with a shebang and files with a Unicode byte order mark are text.
+/// Anything `infer` recognises by its magic bytes is binary, as is anything
+/// containing NUL bytes unless it looks like UTF-16.
+pub fn looks_like_text(head: &[u8]) -> bool {
+    if head.starts_with(b"#!") || Encoding::for_bom(head).is_some() {
+        return true;
+    }
+    if infer::get(head).is_some_and(|kind| !kind.mime_type().starts_with("text/")) {
+        return false;
+    }
+    if head.contains(&0) {
+        return utf16_without_bom(head).is_some();
+    }
+    let control = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+        .count();
+    control * 20 < head.len().max(1)
+}
+
+/// Text read from a file, possibly cut short.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub content: String,
+    /// Whether the file continues past the size limit.
+    pub truncated: bool,
+}
+
+impl Text {
+    /// Wraps text produced by an extractor, cutting it at `limit` bytes.
+    pub fn capped(mut content: String, limit: u64) -> Self {
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        let truncated = content.len() > limit;
+        if truncated {
+            let mut end = limit;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+        }
+        Self { content, truncated }
+    }
+}
+
+/// Picks the encoding of a file from its first bytes, along with the length
+/// of any byte order mark. Returns `None` for binary data.
+///
+/// UTF-8 and UTF-16 (with or without a byte order mark) are recognised
+/// directly. Other 8-bit text uses `fallback` when given, or the legacy
+/// encoding `chardetng` guesses, such as Windows-1252 for Latin-1 text or
+/// Windows-1251 for Cyrillic.
+pub fn detect_encoding(head: &[u8], fallback: Option<&'static Encoding>) -> Option<(&'static Encoding, usize)> {
+    if let Some(found) = Encoding::for_bom(head) {
+        return Some(found);
+    }
+    let head = &head[..head.len().min(SNIFF_LEN)];
+    if let Some(encoding) = utf16_without_bom(head) {
+        return Some((encoding, 0));
+    }
+    if head.contains(&0) {
+        return None;
+    }
+    // A multi-byte character may be cut off at the end of a full sample.
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && head.len() == SNIFF_LEN,
+    };
+    if valid {
+        return Some((encoding_rs::UTF_8, 0));
+    }
+    let encoding = fallback.unwrap_or_else(|| {
+        let mut detector = chardetng::EncodingDetector::new();
+        detector.feed(head, false);
+        detector.guess(None, false)
+    });
+    Some((encoding, 0))
+}
+
+/// Decodes file content held in memory; see [`detect_encoding`].
+pub fn decode(bytes: &[u8], fallback: Option<&'static Encoding>) -> Option<String> {
+    let (encoding, bom_len) = detect_encoding(bytes, fallback)?;
+    Some(encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned())
+}
+
+/// Reads text from `reader` in chunks, keeping at most `limit` bytes of
+/// input, not counting a byte order mark.
+///
+/// Only the decoded text is held in memory, never the whole input, so huge
+/// files cost no more than the limit. When `sniff` is set, input whose first
+/// bytes do not look like text yields `None`.
+pub fn read_text<R: Read>(
+    mut reader: R,
+    limit: u64,
+    sniff: bool,
+    fallback: Option<&'static Encoding>,
+) -> std::io::Result<Option<Text>> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    (&mut reader).take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    if sniff && !looks_like_text(&head) {
+        return Ok(None);
+    }
+    let Some((encoding, bom_len)) = detect_encoding(&head, fallback) else {
+        return Ok(None);
+    };
+
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut content = String::new();
+    let mut remaining = limit;
+    let mut chunk = head.split_off(bom_len.min(head.len()));
+    let mut buf = vec![0; CHUNK_LEN];
+    let truncated = loop {
+        let take = chunk.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let over = take < chunk.len();
+        remaining -= take as u64;
+        let last = over || remaining == 0;
+        let mut input = &chunk[..take];
+        loop {
+            if let Some(needed) = decoder.max_utf8_buffer_length(input.len()) {
+                content.reserve(needed);
+            }
+            let (result, read, _) = decoder.decode_to_string(input, &mut content, last);
+            input = &input[read..];
+            if result == encoding_rs::CoderResult::InputEmpty {
+                break;
+            }
+        }
+        if over {
+            break true;
+        }
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            if !last {
+                content.reserve(decoder.max_utf8_buffer_length(0).unwrap_or(4));
+                let _ = decoder.decode_to_string(&[], &mut content, true);
+            }
+            break false;
+        }
+        if remaining == 0 {
+            break true;
+        }
+        chunk.clear();
+        chunk.extend_from_slice(&buf[..n]);
+    };
+    Ok(Some(Text { content, truncated }))
+}
+
+/// Looks up an encoding by a WHATWG label such as `latin1` or `windows-1251`.
+pub fn encoding_for_label(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+/// Recognises UTF-16 without a byte order mark by its NUL bytes: ASCII-range
+/// text has a zero in every other byte, on the odd side for little endian
+/// and the even side for big endian.
+fn utf16_without_bom(head: &[u8]) -> Option<&'static Encoding> {
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let even = head.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if odd * 10 >= pairs * 7 && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 7 && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_text() {
+        assert!(looks_like_text(b"all: build\n\tcargo build\n"));
+        assert!(looks_like_text(b"#!/bin/sh\necho hi\n"));
+        assert!(looks_like_text(b"h\0i\0 \0t\0h\0e\0r\0e\0"));
+        assert!(!looks_like_text(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!looks_like_text(b"\x7fELF\x02\x01\x01\0\0\0\0\0"));
+        assert!(!looks_like_text(b"abc\0\x01\x02def\0\0\x03"));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("plain ünïcode".as_bytes(), None).unwrap(), "plain ünïcode");
+        assert_eq!(decode(b"\xff\xfeh\0i\0", None).unwrap(), "hi");
+        assert_eq!(decode(b"\0h\0i\0!", None).unwrap(), "hi!");
+        assert_eq!(decode(b"caf\xe9 cr\xe8me br\xfbl\xe9e", None).unwrap(), "café crème brûlée");
+        assert_eq!(
+            decode(b"\xcf\xf0\xe8\xe2\xe5\xf2, \xec\xe8\xf0! \xdd\xf2\xee \xf2\xe5\xf1\xf2.", None).unwrap(),
+            "Привет, мир! Это тест."
+        );
+        assert_eq!(
+            decode(b"caf\xe9", encoding_for_label("windows-1251")).unwrap(),
+            "cafй"
+        );
+        assert_eq!(decode(b"\x01\0\x02\0\0\0\x03", None), None);
+    }
+
+    #[test]
+    fn test_read_text_limit() {
+        let input = "ab\u{e9}cd".repeat(30_000);
+
+        let whole = read_text(input.as_bytes(), u64::MAX, true, None).unwrap().unwrap();
+        assert_eq!(whole, Text { content: input.clone(), truncated: false });
+
+        let exact = read_text(input.as_bytes(), input.len() as u64, true, None).unwrap().unwrap();
+        assert!(!exact.truncated);
+
+        let cut = read_text(input.as_bytes(), 100_001, true, None).unwrap().unwrap();
+        assert!(cut.truncated);
+        assert!(input.starts_with(cut.content.trim_end_matches('\u{fffd}')));
+        assert!(cut.content.len() <= 100_003);
+
+        let wide: Vec<u8> = b"\xff\xfe".iter().copied().chain("hi there".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        let text = read_text(&wide[..], 8, true, None).unwrap().unwrap();
+        assert_eq!(text, Text { content: "hi t".to_string(), truncated: true });
+
+        assert_eq!(read_text(&b"\x7fELF\x02\x01\x01\0\0"[..], u64::MAX, true, None).unwrap(), None);
+    }
+}
@@ -0,0 +1,487 @@
+use crate::indexer::innermost_root;
+use crate::{shutdown, DatabaseStats, DuplicateFilter, DuplicateReport, IndexOptions, IndexSettings, Indexer, Root, SearchFilter, SearchResult};
+use anyhow::{anyhow, bail, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+/// How long a client waits for the daemon to answer a quick request.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns the socket a daemon serving `db` listens on.
+pub fn socket_path(db: &str) -> PathBuf {
+    PathBuf::from(format!("{}.sock", db.trim_end_matches('/')))
+}
+
+/// A request sent to the daemon, one JSON object per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    Ping,
+    Search { filter: SearchFilter },
+    Stats,
+    Duplicates { filter: DuplicateFilter },
+    Index {
+        #[serde(with = "crate::models::os_path")]
+        path: PathBuf,
+        #[serde(default)]
+        resume: bool,
+    },
+    Remove {
+        #[serde(with = "crate::models::os_path")]
+        path: PathBuf,
+    },
+    Reindex {
+        #[serde(with = "crate::models::os_path")]
+        path: PathBuf,
+    },
+    AddRoot { root: Root },
+    RemoveRoot { root: String },
+    ListRoots,
+}
+
+/// The daemon's answer to a [`DaemonRequest`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Pong,
+    Results { results: Vec<SearchResult> },
+    Stats { stats: DatabaseStats },
+    Duplicates { report: DuplicateReport },
+    Indexed { files: u64 },
+    Removed { files: u64 },
+    Roots { roots: Vec<Root> },
+    Error { message: String },
+}
+
+/// A connection to a running daemon.
+pub struct Client {
+    reader: BufReader<std::os::unix::net::UnixStream>,
+}
+
+impl Client {
+    /// Connects to the daemon serving `db`, or returns `None` when no daemon
+    /// is listening so the caller can open the database directly.
+    pub fn connect(db: &str) -> Option<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(socket_path(db)).ok()?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+        Some(Self {
+            reader: BufReader::new(stream),
+        })
+    }
+
+    pub fn request(&mut self, request: &DaemonRequest) -> Result<DaemonResponse> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.reader.get_mut().write_all(line.as_bytes())?;
+
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply)? == 0 {
+            bail!("daemon closed the connection");
+        }
+        match serde_json::from_str(&reply)? {
+            DaemonResponse::Error { message } => Err(anyhow!(message)),
+            response => Ok(response),
+        }
+    }
+
+    pub fn search(&mut self, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        match self.request(&DaemonRequest::Search { filter: filter.clone() })? {
+            DaemonResponse::Results { results } => Ok(results),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn stats(&mut self) -> Result<DatabaseStats> {
+        match self.request(&DaemonRequest::Stats)? {
+            DaemonResponse::Stats { stats } => Ok(stats),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn duplicates(&mut self, filter: &DuplicateFilter) -> Result<DuplicateReport> {
+        // Hashing large candidates can take longer than a query.
+        self.reader.get_ref().set_read_timeout(None)?;
+        match self.request(&DaemonRequest::Duplicates { filter: filter.clone() })? {
+            DaemonResponse::Duplicates { report } => Ok(report),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Asks the daemon to index `path` and waits until it has finished.
+    pub fn index(&mut self, path: &Path, resume: bool) -> Result<u64> {
+        // Indexing can take far longer than a query.
+        self.reader.get_ref().set_read_timeout(None)?;
+        let path = path.canonicalize()?;
+        match self.request(&DaemonRequest::Index { path, resume })? {
+            DaemonResponse::Indexed { files } => Ok(files),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Asks the daemon to drop `path` and everything below it.
+    pub fn remove(&mut self, path: &Path) -> Result<u64> {
+        let path = std::path::absolute(path)?;
+        match self.request(&DaemonRequest::Remove { path })? {
+            DaemonResponse::Removed { files } => Ok(files),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Registers a root, or updates its settings, and starts watching it.
+    pub fn add_root(&mut self, mut root: Root) -> Result<Root> {
+        root.path = root.path.canonicalize()?;
+        match self.request(&DaemonRequest::AddRoot { root })? {
+            DaemonResponse::Roots { mut roots } if roots.len() == 1 => Ok(roots.remove(0)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Unregisters a root by name or path and drops its files.
+    pub fn remove_root(&mut self, root: &str) -> Result<u64> {
+        match self.request(&DaemonRequest::RemoveRoot { root: root.to_string() })? {
+            DaemonResponse::Removed { files } => Ok(files),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn roots(&mut self) -> Result<Vec<Root>> {
+        match self.request(&DaemonRequest::ListRoots)? {
+            DaemonResponse::Roots { roots } => Ok(roots),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Asks the daemon to re-index `path` and waits until it has finished.
+    pub fn reindex(&mut self, path: &Path) -> Result<u64> {
+        self.reader.get_ref().set_read_timeout(None)?;
+        let path = path.canonicalize()?;
+        match self.request(&DaemonRequest::Reindex { path })? {
+            DaemonResponse::Indexed { files } => Ok(files),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: DaemonResponse) -> anyhow::Error {
+    anyhow!("unexpected daemon response: {:?}", response)
+}
+
+struct DaemonState {
+    indexer: Arc<Indexer>,
+    watcher: Mutex<RecommendedWatcher>,
+    /// Raised on shutdown so indexing requests checkpoint and stop.
+    stop: Arc<AtomicBool>,
+}
+
+/// Runs the daemon for `db` until interrupted.
+///
+/// The daemon holds sled's exclusive lock, answers clients on
+/// [`socket_path`] and keeps every registered root up to date by watching
+/// it for changes.
+pub async fn run(db: &str, settings: IndexSettings) -> Result<()> {
+    let socket = socket_path(db);
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(&socket).is_ok() {
+            bail!("a daemon is already serving {} on {}", db, socket.display());
+        }
+        std::fs::remove_file(&socket)?;
+    }
+
+    let indexer = Arc::new(Indexer::with_settings(db, settings)?);
+    let listener = UnixListener::bind(&socket)?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+
+    let result = serve(listener, indexer, db).await;
+    let _ = std::fs::remove_file(&socket);
+    result
+}
+
+async fn serve(listener: UnixListener, indexer: Arc<Indexer>, db: &str) -> Result<()> {
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        Ok(event) => {
+            let _ = events_tx.send(event);
+        }
+        Err(e) => warn!(error = %e, "file watcher error"),
+    })?;
+
+    let state = Arc::new(DaemonState {
+        indexer: indexer.clone(),
+        watcher: Mutex::new(watcher),
+        stop: Arc::new(AtomicBool::new(false)),
+    });
+    for root in indexer.roots()? {
+        watch_root(&state, &root);
+    }
+
+    // The database may live under a watched root; never react to its own writes.
+    let ignored = vec![
+        std::fs::canonicalize(db)?,
+        std::fs::canonicalize(socket_path(db))?,
+    ];
+    tokio::spawn(apply_changes(indexer, events_rx, ignored, state.stop.clone()));
+
+    info!(db, "daemon ready");
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _addr) = accepted?;
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_client(stream, state).await {
+                        debug!(error = %e, "client connection ended");
+                    }
+                });
+            }
+            _ = shutdown::wait() => {
+                info!("daemon shutting down");
+                state.stop.store(true, Ordering::Relaxed);
+                state.indexer.vacuum()?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn watch_root(state: &DaemonState, root: &Path) {
+    let mut watcher = state.watcher.lock().unwrap_or_else(|e| e.into_inner());
+    match watcher.watch(root, RecursiveMode::Recursive) {
+        Ok(()) => info!(root = %root.display(), "watching root"),
+        Err(e) => warn!(root = %root.display(), error = %e, "cannot watch root"),
+    }
+}
+
+async fn handle_client(stream: UnixStream, state: Arc<DaemonState>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<DaemonRequest>(&line) {
+            Ok(request) => handle_request(request, &state).await,
+            Err(e) => DaemonResponse::Error {
+                message: format!("invalid request: {}", e),
+            },
+        };
+
+        let mut reply = serde_json::to_string(&response)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn handle_request(request: DaemonRequest, state: &Arc<DaemonState>) -> DaemonResponse {
+    let indexer = state.indexer.clone();
+    let outcome = match request {
+        DaemonRequest::Ping => return DaemonResponse::Pong,
+        DaemonRequest::Search { filter } => {
+            tokio::task::spawn_blocking(move || indexer.search(&filter))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|r| r)
+                .map(|results| DaemonResponse::Results { results })
+        }
+        DaemonRequest::Stats => tokio::task::spawn_blocking(move || indexer.get_stats())
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r)
+            .map(|stats| DaemonResponse::Stats { stats }),
+        DaemonRequest::Duplicates { filter } => tokio::task::spawn_blocking(move || indexer.duplicates(&filter))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r)
+            .map(|report| DaemonResponse::Duplicates { report }),
+        DaemonRequest::Index { path, resume } => {
+            let runtime = tokio::runtime::Handle::current();
+            let root = path.clone();
+            let stop = state.stop.clone();
+            let outcome = tokio::task::spawn_blocking(move || {
+                let options = IndexOptions {
+                    resume,
+                    stop: Some(&stop),
+                    ..Default::default()
+                };
+                runtime.block_on(indexer.index_dir_with(&root, &options))
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r);
+            if outcome.is_ok() {
+                watch_root(state, &path);
+            }
+            outcome.map(|outcome| DaemonResponse::Indexed { files: outcome.files })
+        }
+        DaemonRequest::Remove { path } => tokio::task::spawn_blocking(move || indexer.remove_path(&path))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|r| r)
+            .map(|files| DaemonResponse::Removed { files }),
+        DaemonRequest::Reindex { path } => {
+            let runtime = tokio::runtime::Handle::current();
+            let stop = state.stop.clone();
+            tokio::task::spawn_blocking(move || runtime.block_on(indexer.reindex_path(&path, Some(&stop))))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|r| r)
+                .map(|files| DaemonResponse::Indexed { files })
+        }
+        DaemonRequest::AddRoot { root } => {
+            let registered = indexer.register_root(root);
+            if let Ok(root) = &registered {
+                watch_root(state, &root.path);
+            }
+            registered.map(|root| DaemonResponse::Roots { roots: vec![root] })
+        }
+        DaemonRequest::RemoveRoot { root } => {
+            let path = indexer.find_root(&root).ok().flatten().map(|r| r.path);
+            let removed = tokio::task::spawn_blocking(move || indexer.remove_root(&root))
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|r| r);
+            if let (Ok(_), Some(path)) = (&removed, path) {
+                let mut watcher = state.watcher.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = watcher.unwatch(&path);
+            }
+            removed.map(|files| DaemonResponse::Removed { files })
+        }
+        DaemonRequest::ListRoots => indexer.root_list().map(|roots| DaemonResponse::Roots { roots }),
+    };
+
+    outcome.unwrap_or_else(|e| DaemonResponse::Error {
+        message: e.to_string(),
+    })
+}
+
+/// Re-indexes or drops files as the watcher reports changes under the roots.
+///
+/// Events are applied one at a time on the blocking pool, since a created
+/// directory is walked and every file under it read.
+async fn apply_changes(
+    indexer: Arc<Indexer>,
+    mut events: mpsc::UnboundedReceiver<Event>,
+    ignored: Vec<PathBuf>,
+    stop: Arc<AtomicBool>,
+) {
+    let ignored = Arc::new(ignored);
+    while let Some(event) = events.recv().await {
+        if !matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            continue;
+        }
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let runtime = tokio::runtime::Handle::current();
+        let (indexer, ignored, stop) = (indexer.clone(), ignored.clone(), stop.clone());
+        let applied = tokio::task::spawn_blocking(move || {
+            runtime.block_on(apply_event(&indexer, event, &ignored, &stop))
+        })
+        .await;
+        if let Err(e) = applied {
+            error!(error = %e, "cannot apply file changes");
+        }
+    }
+}
+
+async fn apply_event(indexer: &Indexer, event: Event, ignored: &[PathBuf], stop: &AtomicBool) {
+    // Roots can be added or removed while the daemon runs, so they are
+    // read again for every event, but only once for all its paths.
+    let roots = match indexer.root_list() {
+        Ok(roots) => roots,
+        Err(e) => {
+            error!(error = %e, "cannot read roots");
+            return;
+        }
+    };
+    for path in event.paths {
+        if ignored.iter().any(|prefix| path.starts_with(prefix)) || indexer.is_excluded_in(&path, &roots) {
+            continue;
+        }
+        let Some(root) = innermost_root(&roots, &path) else {
+            continue;
+        };
+
+        if path.is_file() {
+            if let Err(e) = indexer.index_file(&path, root).await {
+                debug!(path = %path.display(), error = %e, "cannot index changed file");
+            }
+        } else if path.is_dir() {
+            if matches!(event.kind, EventKind::Create(_)) {
+                for entry in WalkDir::new(&path)
+                    .into_iter()
+                    .filter_entry(|e| !indexer.is_excluded_in(e.path(), &roots))
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().is_file())
+                {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let _ = indexer.index_file(entry.path(), root).await;
+                }
+            }
+        } else if let Err(e) = indexer.remove_path(&path) {
+            error!(path = %path.display(), error = %e, "cannot drop removed file");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_client_round_trip() {
+        let dir = TempDir::new("daemon");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "daemon notes").unwrap();
+
+        let db = dir.db();
+        let indexer = Arc::new(Indexer::new(&db).unwrap());
+        indexer.index_dir(&root).await.unwrap();
+
+        let listener = UnixListener::bind(socket_path(&db)).unwrap();
+        let served = db.clone();
+        tokio::spawn(async move { serve(listener, indexer, &served).await });
+
+        let db_for_client = db.clone();
+        let results = tokio::task::spawn_blocking(move || {
+            let mut client = Client::connect(&db_for_client).expect("daemon is listening");
+            assert!(matches!(client.request(&DaemonRequest::Ping).unwrap(), DaemonResponse::Pong));
+            assert_eq!(client.stats().unwrap().total_files, 1);
+            assert_eq!(client.reindex(&root).unwrap(), 1);
+            client.search(&SearchFilter {
+                query: "notes".to_string(),
+                max_size: u64::MAX,
+                limit: 10,
+                ..Default::default()
+            })
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("notes.txt"));
+    }
+
+    #[test]
+    fn test_connect_without_daemon() {
+        assert!(Client::connect("/nonexistent/rust-search.db").is_none());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::models::{DuplicateFile, DuplicateGroup, DuplicateReport, FileInfo};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Bytes hashed from each end of a file for its partial hash.
+pub const PARTIAL_LEN: u64 = 16 * 1024;
+
+/// MD5 of the first and last [`PARTIAL_LEN`] bytes of the file at `path`.
+///
+/// Files up to twice that size are hashed whole, so for them the partial
+/// hash is also the full hash.
+pub fn partial_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size <= 2 * PARTIAL_LEN {
+        return hash_reader(file);
+    }
+    let mut context = md5::Context::new();
+    let mut buf = vec![0; PARTIAL_LEN as usize];
+    file.read_exact(&mut buf)?;
+    context.consume(&buf);
+    file.seek(SeekFrom::End(-(PARTIAL_LEN as i64)))?;
+    file.read_exact(&mut buf)?;
+    context.consume(&buf);
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// MD5 of the whole file at `path`.
+pub fn full_hash(path: &Path) -> io::Result<String> {
+    hash_reader(File::open(path)?)
+}
+
+fn hash_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut context = md5::Context::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => context.consume(&buf[..n]),
+        }
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// Groups `files` by identical content.
+///
+/// Work is narrowed in three passes: files are grouped by size, then the
+/// groups are split by partial hash, using the stored one when indexing
+/// recorded it, and only the files still sharing a group are read in full.
+/// Stored hashes may be stale, so they only pick candidates; every reported
+/// file has been hashed in full from disk. Files that changed size since they
+/// were indexed or can no longer be read are left out.
+pub fn find_duplicates(files: Vec<FileInfo>) -> DuplicateReport {
+    let mut by_size: HashMap<u64, Vec<FileInfo>> = HashMap::new();
+    for file in files {
+        by_size.entry(file.size).or_default().push(file);
+    }
+
+    let mut groups = Vec::new();
+    for (size, files) in by_size {
+        if files.len() < 2 {
+            continue;
+        }
+        let mut by_partial: HashMap<String, Vec<FileInfo>> = HashMap::new();
+        for file in files {
+            let path = file.os_path();
+            if std::fs::metadata(&path).map(|m| m.len()).ok() != Some(size) {
+                continue;
+            }
+            let hash = match &file.partial_hash {
+                Some(hash) => hash.clone(),
+                None => match partial_hash(&path) {
+                    Ok(hash) => hash,
+                    Err(_) => continue,
+                },
+            };
+            by_partial.entry(hash).or_default().push(file);
+        }
+
+        for files in by_partial.into_values() {
+            if files.len() < 2 {
+                continue;
+            }
+            let mut by_full: HashMap<String, Vec<FileInfo>> = HashMap::new();
+            for file in files {
+                if let Ok(hash) = full_hash(&file.os_path()) {
+                    by_full.entry(hash).or_default().push(file);
+                }
+            }
+            groups.extend(by_full.into_iter().filter(|(_, files)| files.len() > 1).map(|(hash, files)| {
+                let mut files: Vec<DuplicateFile> = files
+                    .into_iter()
+                    .map(|file| DuplicateFile {
+                        file_id: file.id,
+                        path: file.path,
+                        path_bytes: file.path_bytes,
+                        modified_at: file.modified_at,
+                    })
+                    .collect();
+                files.sort_by(|a, b| a.path.cmp(&b.path));
+                DuplicateGroup { hash, size, wasted: size * (files.len() as u64 - 1), files }
+            }));
+        }
+    }
+
+    groups.sort_by(|a, b| b.wasted.cmp(&a.wasted).then_with(|| a.files[0].path.cmp(&b.files[0].path)));
+    DuplicateReport {
+        duplicate_files: groups.iter().map(|group| group.files.len() - 1).sum(),
+        wasted: groups.iter().map(|group| group.wasted).sum(),
+        groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_partial_hash() {
+        let dir = TempDir::new("hash");
+
+        let small = dir.join("small");
+        std::fs::write(&small, "hello").unwrap();
+        assert_eq!(partial_hash(&small).unwrap(), full_hash(&small).unwrap());
+        assert_eq!(full_hash(&small).unwrap(), "5d41402abc4b2a76b9719d911017c592");
+
+        // Same ends, different middles: only the full hash tells them apart.
+        let mut a = vec![b'x'; 3 * PARTIAL_LEN as usize];
+        std::fs::write(dir.join("a"), &a).unwrap();
+        a[PARTIAL_LEN as usize + 1] = b'y';
+        std::fs::write(dir.join("b"), &a).unwrap();
+        assert_eq!(partial_hash(&dir.join("a")).unwrap(), partial_hash(&dir.join("b")).unwrap());
+        assert_ne!(full_hash(&dir.join("a")).unwrap(), full_hash(&dir.join("b")).unwrap());
+    }
+}
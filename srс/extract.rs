@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+
+/// Documents larger than this are indexed by name only, since extraction
+/// needs the whole file in memory.
+pub const MAX_DOCUMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Produces searchable text and metadata for a file type the indexer does
+/// not read as plain text.
+///
+/// Extractors are tried newest first, and the first one claiming either the
+/// file's extension or the MIME type sniffed from its first bytes is used.
+/// Register them with
+/// [`Indexer::register_extractor`](crate::Indexer::register_extractor);
+/// later registrations win over earlier ones and over the built-in PDF and
+/// office extractors.
+///
+/// ```
+/// use rust_search::{Extracted, Extractor};
+///
+/// struct Sketch;
+///
+/// impl Extractor for Sketch {
+///     fn name(&self) -> &str {
+///         "sketch"
+///     }
+///
+///     fn extensions(&self) -> &[&str] {
+///         &["sketch"]
+///     }
+///
+///     fn fields(&self) -> &[&str] {
+///         &["layers"]
+///     }
+///
+///     fn extract(&self, bytes: &[u8]) -> anyhow::Result<Extracted> {
+///         let mut extracted = Extracted::text(String::from_utf8_lossy(bytes));
+///         extracted.metadata.insert("layers".to_string(), "1".to_string());
+///         Ok(extracted)
+///     }
+/// }
+/// ```
+pub trait Extractor: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    /// File extensions handled, without the dot; compared ignoring case.
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// MIME types handled, as detected from the file's magic bytes.
+    fn mime_types(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Metadata fields stored, lowercase. Only these are read as
+    /// `field:value` filters in queries; other words with a colon, such as
+    /// `std::io`, stay search text.
+    fn fields(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Extracts from the whole file, which is at most [`MAX_DOCUMENT_SIZE`]
+    /// bytes.
+    fn extract(&self, bytes: &[u8]) -> Result<Extracted>;
+}
+
+/// What an [`Extractor`] found in a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extracted {
+    /// Searchable text; empty when the file has none.
+    pub text: String,
+    /// Extra fields stored with the record, such as a title or author.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Extracted {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), metadata: BTreeMap::new() }
+    }
+}
+
+/// A built-in extractor backed by a plain function.
+#[derive(Clone, Copy)]
+struct Builtin {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    extract: fn(&[u8]) -> Result<String>,
+}
+
+impl Extractor for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<Extracted> {
+        Ok(Extracted::text((self.extract)(bytes)?))
+    }
+}
+
+const BUILTIN: &[Builtin] = &[
+    Builtin { name: "pdf", extensions: &["pdf"], extract: pdf_text },
+    Builtin { name: "docx", extensions: &["docx", "docm", "dotx"], extract: docx_text },
+    Builtin { name: "xlsx", extensions: &["xlsx", "xlsm"], extract: xlsx_text },
+    Builtin { name: "pptx", extensions: &["pptx", "pptm"], extract: pptx_text },
+    Builtin { name: "opendocument", extensions: &["odt", "ods", "odp", "ott"], extract: odf_text },
+];
+
+/// The extractors every indexer starts with.
+pub fn builtin() -> Vec<Box<dyn Extractor>> {
+    let mut extractors: Vec<Box<dyn Extractor>> = BUILTIN
+        .iter()
+        .map(|builtin| Box::new(*builtin) as Box<dyn Extractor>)
+        .collect();
+    extractors.push(Box::new(crate::media::ImageMetadata));
+    extractors.push(Box::new(crate::media::AudioTags));
+    extractors
+}
+
+/// The metadata fields stored by any of `extractors`.
+pub fn fields(extractors: &[Box<dyn Extractor>]) -> Vec<&str> {
+    extractors.iter().flat_map(|e| e.fields().iter().copied()).collect()
+}
+
+/// Picks the extractor for a file: the first entry of `extractors` that
+/// handles its extension or, when `mime` is known, its MIME type.
+pub fn find<'a>(
+    extractors: &'a [Box<dyn Extractor>],
+    extension: &str,
+    mime: Option<&str>,
+) -> Option<&'a dyn Extractor> {
+    extractors
+        .iter()
+        .find(|e| {
+            handles_extension(e.as_ref(), extension)
+                || mime.is_some_and(|mime| e.mime_types().iter().any(|m| m.eq_ignore_ascii_case(mime)))
+        })
+        .map(|e| e.as_ref())
+}
+
+/// Whether [`find`] needs the MIME type to decide: an extractor matching by
+/// MIME type comes before any that handles `extension`.
+pub fn needs_mime(extractors: &[Box<dyn Extractor>], extension: &str) -> bool {
+    extractors
+        .iter()
+        .take_while(|e| !handles_extension(e.as_ref(), extension))
+        .any(|e| !e.mime_types().is_empty())
+}
+
+fn handles_extension(extractor: &dyn Extractor, extension: &str) -> bool {
+    extractor.extensions().iter().any(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn pdf_text(bytes: &[u8]) -> Result<String> {
+    let document = lopdf::Document::load_mem(bytes)?;
+    let pages: Vec<u32> = document.get_pages().into_keys().collect();
+    Ok(document.extract_text(&pages)?)
+}
+
+fn docx_text(bytes: &[u8]) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    xml_text(&zip_entry(&mut zip, "word/document.xml")?, true)
+}
+
+fn xlsx_text(bytes: &[u8]) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    // Cell strings live in the shared table; sheets only hold inline ones.
+    let mut parts = vec!["xl/sharedStrings.xml".to_string()];
+    parts.extend(numbered_entries(&zip, "xl/worksheets/sheet"));
+    let mut text = String::new();
+    for part in parts {
+        if let Ok(xml) = zip_entry(&mut zip, &part) {
+            text.push_str(&xml_text(&xml, true)?);
+        }
+    }
+    Ok(text)
+}
+
+fn pptx_text(bytes: &[u8]) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut text = String::new();
+    for slide in numbered_entries(&zip, "ppt/slides/slide") {
+        text.push_str(&xml_text(&zip_entry(&mut zip, &slide)?, true)?);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn odf_text(bytes: &[u8]) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))?;
+    xml_text(&zip_entry(&mut zip, "content.xml")?, false)
+}
+
+/// Reads one XML part of an OOXML or OpenDocument zip container.
+fn zip_entry(zip: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String> {
+    let entry = zip.by_name(name).with_context(|| format!("missing {}", name))?;
+    let mut xml = String::new();
+    entry.take(MAX_DOCUMENT_SIZE).read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+/// Entries named `<prefix><n>.xml`, in numeric order.
+fn numbered_entries(zip: &zip::ZipArchive<Cursor<&[u8]>>, prefix: &str) -> Vec<String> {
+    let mut entries: Vec<(u32, String)> = zip
+        .file_names()
+        .filter_map(|name| {
+            let n = name.strip_prefix(prefix)?.strip_suffix(".xml")?.parse().ok()?;
+            Some((n, name.to_string()))
+        })
+        .collect();
+    entries.sort();
+    entries.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Collects the character data of a document's XML, ending paragraphs and
+/// rows with line breaks.
+///
+/// OOXML keeps visible text in `t` elements (`w:t`, `a:t`), so with
+/// `only_t` everything else, such as formulas and numbers, is skipped.
+/// OpenDocument puts text directly in its paragraphs.
+fn xml_text(xml: &str, only_t: bool) -> Result<String> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut text = String::new();
+    let mut in_t = false;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.local_name().as_ref() == b"t" => in_t = true,
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_t = false,
+                b"p" | b"h" | b"si" | b"row" | b"table-row" => text.push('\n'),
+                b"c" | b"table-cell" => text.push('\t'),
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"tab" => text.push('\t'),
+                b"br" | b"line-break" => text.push('\n'),
+                b"s" if !only_t => text.push(' '),
+                _ => {}
+            },
+            Event::Text(t) if in_t || !only_t => text.push_str(&t.unescape()?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn zip_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_office_text() {
+        let docx = zip_of(&[(
+            "word/document.xml",
+            r#"<w:document xmlns:w="w"><w:body><w:p><w:r><w:t>Quarterly</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">report &amp; plan</w:t></w:r></w:p><w:p><w:r><w:t>Second</w:t></w:r></w:p></w:body></w:document>"#,
+        )]);
+        let builtin = builtin();
+        let docx_extractor = find(&builtin, "DOCX", None).unwrap();
+        assert_eq!(docx_extractor.name(), "docx");
+        assert_eq!(docx_extractor.extract(&docx).unwrap(), Extracted::text("Quarterly\treport & plan\nSecond\n"));
+
+        let xlsx = zip_of(&[
+            ("xl/sharedStrings.xml", r#"<sst><si><t>Revenue</t></si><si><t>Costs</t></si></sst>"#),
+            ("xl/worksheets/sheet1.xml", r#"<worksheet><sheetData><row><c t="s"><v>0</v></c><c t="inlineStr"><is><t>Inline</t></is></c></row></sheetData></worksheet>"#),
+        ]);
+        let text = xlsx_text(&xlsx).unwrap();
+        assert!(text.contains("Revenue") && text.contains("Costs") && text.contains("Inline"));
+        assert!(!text.contains('0'));
+
+        let pptx = zip_of(&[
+            ("ppt/slides/slide10.xml", r#"<p:sld><a:p><a:r><a:t>Last</a:t></a:r></a:p></p:sld>"#),
+            ("ppt/slides/slide2.xml", r#"<p:sld><a:p><a:r><a:t>First</a:t></a:r></a:p></p:sld>"#),
+        ]);
+        assert_eq!(pptx_text(&pptx).unwrap(), "First\n\nLast\n\n");
+
+        let odt = zip_of(&[(
+            "content.xml",
+            r#"<office:document-content><office:body><text:h>Title</text:h><text:p>Hello<text:s/>world</text:p></office:body></office:document-content>"#,
+        )]);
+        assert_eq!(odf_text(&odt).unwrap(), "Title\nHello world\n");
+
+        assert!(find(&builtin, "rs", Some("text/plain")).is_none());
+        assert!(docx_text(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn test_find_order() {
+        struct Scanner;
+
+        impl Extractor for Scanner {
+            fn name(&self) -> &str {
+                "scanner"
+            }
+
+            fn mime_types(&self) -> &[&str] {
+                &["application/pdf"]
+            }
+
+            fn extract(&self, _bytes: &[u8]) -> Result<Extracted> {
+                Ok(Extracted::default())
+            }
+        }
+
+        let mut extractors = builtin();
+        assert!(!needs_mime(&extractors, "pdf"));
+        extractors.insert(0, Box::new(Scanner));
+        assert!(needs_mime(&extractors, "pdf"));
+        assert!(!needs_mime(&builtin(), "rs"));
+        assert_eq!(find(&extractors, "pdf", Some("application/pdf")).unwrap().name(), "scanner");
+        assert_eq!(find(&extractors, "pdf", Some("image/png")).unwrap().name(), "pdf");
+        assert_eq!(find(&extractors, "bin", Some("application/pdf")).unwrap().name(), "scanner");
+    }
+
+    #[test]
+    fn test_pdf_text() {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{dictionary, Document, Object, Stream};
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let resources_id = doc.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 24.into()]),
+                Operation::new("Td", vec![100.into(), 600.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Invoice pdfneedle")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+
+        assert!(pdf_text(&bytes).unwrap().contains("Invoice pdfneedle"));
+        assert!(pdf_text(b"%PDF-1.5 truncated").is_err());
+    }
+}
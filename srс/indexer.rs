@@ -1,52 +1,314 @@
 use sled::Db;
-use crate::models::{SearchResult, SearchFilter, FileInfo, DatabaseStats};
+use crate::models::{self, SearchResult, SearchFilter, FileInfo, DatabaseStats, Checkpoint, IndexSettings, Root, ContentPolicy, DuplicateFilter, DuplicateReport};
 use crate::search_engine::SearchEngine;
+use crate::metrics::IndexingMetrics;
+use crate::archive::{self, ArchiveKind};
+use crate::content;
+use crate::dupes;
+use crate::record;
+use crate::schema;
+use crate::extract::{self, Extractor};
 use anyhow::Result;
-use std::path::Path;
+use encoding_rs::Encoding;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
-use chrono::Utc;
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Derives the record key for a file from its path.
+///
+/// The hash covers the exact OS bytes of the path, so names that differ only
+/// in bytes that are not valid UTF-8 still get distinct records.
+fn file_id(path: &Path) -> String {
+    format!("{:x}", md5::compute(models::path_bytes(path)))
+}
+
+/// Matches a file name against a pattern where `*` matches any run of
+/// characters and `?` exactly one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// Makes `path` absolute without requiring it to exist, resolving symlinks
+/// when it does.
+fn absolute(path: &Path) -> Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(path) => Ok(path),
+        Err(_) if path.is_absolute() => Ok(path.to_path_buf()),
+        Err(_) => Ok(std::env::current_dir()?.join(path)),
+    }
+}
+
+/// The key prefix shared by every path strictly below `path`.
+fn subtree_prefix(path: &Path) -> Vec<u8> {
+    let mut prefix = models::path_bytes(path);
+    let separator = std::path::MAIN_SEPARATOR as u8;
+    if prefix.last() != Some(&separator) {
+        prefix.push(separator);
+    }
+    prefix
+}
+
+/// Whether `extension` is in `configured`, or in `builtin` when nothing is
+/// configured.
+fn extension_listed(configured: &[String], builtin: &[&str], extension: &str) -> bool {
+    if configured.is_empty() {
+        builtin.iter().any(|ext| ext.eq_ignore_ascii_case(extension))
+    } else {
+        configured
+            .iter()
+            .any(|ext| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+    }
+}
+
+/// Reads a root record. Databases from before named roots stored only the
+/// completion time, so those values become a root with default settings.
+pub(crate) fn decode_root(key: &[u8], value: &[u8]) -> Root {
+    serde_json::from_slice(value).unwrap_or_else(|_| {
+        let mut root = Root::new(models::path_from_bytes(key));
+        root.last_indexed_at = std::str::from_utf8(value)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+        root
+    })
+}
+
+/// The innermost of `roots` containing `path`.
+pub fn innermost_root<'a>(roots: &'a [Root], path: &Path) -> Option<&'a Root> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(&root.path))
+        .max_by_key(|root| root.path.as_os_str().len())
+}
+
+/// How many files are indexed between saved checkpoints.
+const CHECKPOINT_INTERVAL: u64 = 1000;
+
+/// How many records are scanned between checks of the cancel flag.
+const CANCEL_CHECK_INTERVAL: usize = 256;
+
+/// Controls a single [`Indexer::index_dir_with`] run.
+#[derive(Default)]
+pub struct IndexOptions<'a> {
+    /// Continue after the checkpoint left by an interrupted run, if any.
+    pub resume: bool,
+    /// Raised to stop the walk at the next file.
+    pub stop: Option<&'a AtomicBool>,
+    /// Called with the number of files indexed so far in this run.
+    pub progress: Option<&'a (dyn Fn(u64) + Sync)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IndexOutcome {
+    /// Files indexed by this run, excluding those covered by a checkpoint.
+    pub files: u64,
+    /// False when the run was stopped and left a checkpoint behind.
+    pub completed: bool,
+}
 
 pub struct Indexer {
     db: Db,
+    /// Maps each record's path to its id, ordered so a subtree is a prefix range.
+    paths: sled::Tree,
+    roots: sled::Tree,
+    checkpoints: sled::Tree,
     engine: SearchEngine,
+    metrics: IndexingMetrics,
+    settings: IndexSettings,
+    fallback_encoding: Option<&'static Encoding>,
+    extractors: Vec<Box<dyn Extractor>>,
+}
+
+/// What was read from a file: its text, if any, and extractor metadata.
+#[derive(Default)]
+struct Content {
+    text: Option<content::Text>,
+    metadata: BTreeMap<String, String>,
 }
 
 impl Indexer {
     pub fn new(path: &str) -> Result<Self> {
-        let db = sled::open(path)?;
+        Self::with_settings(path, IndexSettings::default())
+    }
+
+    /// Opens the database at `path`, indexing according to `settings`.
+    pub fn with_settings(path: &str, settings: IndexSettings) -> Result<Self> {
+        let fallback_encoding = match &settings.fallback_encoding {
+            Some(label) => match content::encoding_for_label(label) {
+                Some(encoding) => Some(encoding),
+                None => anyhow::bail!("unknown encoding `{}`", label),
+            },
+            None => None,
+        };
+        let db = schema::open(path)?;
+        let paths = db.open_tree("paths")?;
+        let roots = db.open_tree("roots")?;
+        let checkpoints = db.open_tree("checkpoints")?;
         let engine = SearchEngine::new();
-        Ok(Self { db, engine })
+        let indexer = Self {
+            db,
+            paths,
+            roots,
+            checkpoints,
+            engine,
+            metrics: IndexingMetrics::default(),
+            settings,
+            fallback_encoding,
+            extractors: extract::builtin(),
+        };
+        Ok(indexer)
+    }
+
+    /// Adds an extractor for files the built-in ones do not handle. It takes
+    /// precedence over every extractor registered before it.
+    pub fn register_extractor(&mut self, extractor: impl Extractor + 'static) {
+        self.extractors.insert(0, Box::new(extractor));
+    }
+
+    /// Writes the record for a file and its path entry.
+    fn store(&self, file_info: &FileInfo) -> Result<()> {
+        self.db.insert(file_info.id.as_bytes(), record::encode(file_info))?;
+        self.paths.insert(models::path_bytes(&file_info.os_path()), file_info.id.as_bytes())?;
+        Ok(())
     }
 
-    pub async fn index_dir(&self, path: &Path) -> Result<()> {
-        println!("📚 Indexing directory: {}", path.display());
+    pub async fn index_dir(&self, path: &Path) -> Result<u64> {
+        Ok(self.index_dir_with(path, &IndexOptions::default()).await?.files)
+    }
+
+    /// Indexes `path`, saving a checkpoint so an interrupted run can resume.
+    ///
+    /// The walk is sorted by file name, which makes its order match `Path`
+    /// ordering; resuming skips every entry up to the checkpointed path and
+    /// prunes directories that lie entirely before it. When `options.stop` is
+    /// raised the run flushes, records a checkpoint and returns early.
+    pub async fn index_dir_with(&self, path: &Path, options: &IndexOptions<'_>) -> Result<IndexOutcome> {
+        // Store absolute paths so records stay valid from any working directory.
+        let path = &path.canonicalize()?;
+        let root = self.add_root(path)?;
+
+        let resume_from = if options.resume { self.checkpoint(path)? } else { None };
+        let previous = resume_from.as_ref().map_or(0, |c| c.files_indexed);
+        match &resume_from {
+            Some(checkpoint) => info!(
+                path = %path.display(),
+                after = %checkpoint.last_path.display(),
+                files = previous,
+                "resuming indexing"
+            ),
+            None => info!(path = %path.display(), "indexing directory"),
+        }
+
+        let started = Instant::now();
         let mut count = 0;
+        let mut last_path: Option<PathBuf> = None;
+        let mut walker = WalkDir::new(path).sort_by_file_name().into_iter();
+
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+
+            if let Some(checkpoint) = &resume_from {
+                if entry.path() <= checkpoint.last_path.as_path() {
+                    if entry.file_type().is_dir() && !checkpoint.last_path.starts_with(entry.path()) {
+                        walker.skip_current_dir();
+                    }
+                    continue;
+                }
+            }
+
+            if entry.depth() > 0 && self.is_excluded_name(&entry.file_name().to_string_lossy(), Some(&root)) {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+
+            if options.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                if let Some(last) = &last_path {
+                    self.save_checkpoint(path, last, previous + count)?;
+                }
+                self.db.flush()?;
+                info!(path = %path.display(), files = previous + count, "indexing interrupted");
+                return Ok(IndexOutcome { files: count, completed: false });
+            }
 
-        for entry in WalkDir::new(path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_file())
-        {
             let file_path = entry.path();
-            if let Ok(file_info) = self.create_file_info(file_path).await {
-                let id = file_info.id.clone();
-                let json = serde_json::to_string(&file_info)?;
-                self.db.insert(id.as_bytes(), json.as_bytes())?;
+            if !file_path.is_file() {
+                continue;
+            }
+
+            if self.index_file(file_path, &root).await.is_ok() {
+                self.metrics.files_indexed.fetch_add(1, Ordering::Relaxed);
                 count += 1;
 
-                if count % 1000 == 0 {
-                    println!("  ✓ Indexed {} files", count);
+                if let Some(progress) = options.progress {
+                    progress(count);
+                }
+                if count % CHECKPOINT_INTERVAL == 0 {
+                    self.save_checkpoint(path, file_path, previous + count)?;
+                    info!(files = previous + count, "indexing progress");
                 }
             }
+            last_path = Some(file_path.to_path_buf());
         }
 
+        let pruned = self.prune(path)?;
         self.db.flush()?;
-        println!("✅ Indexing complete! Total: {} files", count);
+        self.mark_indexed(path)?;
+        self.metrics.record_run(count, started.elapsed());
+        info!(
+            path = %path.display(),
+            files = previous + count,
+            pruned,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "indexing complete"
+        );
+        Ok(IndexOutcome { files: count, completed: true })
+    }
+
+    /// Returns the saved progress of an interrupted run over `root`.
+    pub fn checkpoint(&self, root: &Path) -> Result<Option<Checkpoint>> {
+        let root = root.canonicalize()?;
+        match self.checkpoints.get(models::path_bytes(&root))? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Records that every entry of `root` up to `last_path` is indexed.
+    ///
+    /// sled persists writes in order, so once the checkpoint is flushed the
+    /// records it covers are durable too.
+    fn save_checkpoint(&self, root: &Path, last_path: &Path, files_indexed: u64) -> Result<()> {
+        let checkpoint = Checkpoint {
+            last_path: last_path.to_path_buf(),
+            files_indexed,
+            updated_at: Utc::now().to_rfc3339(),
+        };
+        self.checkpoints.insert(models::path_bytes(root), serde_json::to_vec(&checkpoint)?)?;
+        self.checkpoints.flush()?;
         Ok(())
     }
 
-    async fn create_file_info(&self, path: &Path) -> Result<FileInfo> {
+    async fn create_file_info(&self, path: &Path, policy: ContentPolicy) -> Result<FileInfo> {
         let metadata = std::fs::metadata(path)?;
         let extension = path
             .extension()
@@ -54,71 +316,546 @@ impl Indexer {
             .unwrap_or("unknown")
             .to_string();
 
-        let content = if self.is_text_file(&extension) {
-            std::fs::read_to_string(path).ok()
-        } else {
-            None
+        // Compressed files are read through the decompressor and judged by
+        // the name they had before compression.
+        let content = match archive::decompressed_name(path) {
+            Some(inner) => {
+                let extension = inner.extension().and_then(|s| s.to_str()).unwrap_or("unknown");
+                self.read_content(extension, policy, || archive::decompress(path))
+            }
+            None => self.read_content(&extension, policy, || std::fs::File::open(path)),
         };
 
         Ok(FileInfo {
-            id: format!("{:x}", md5::compute(path.to_string_lossy().as_bytes())),
+            id: file_id(path),
             path: path.to_string_lossy().to_string(),
+            path_bytes: models::raw_path_bytes(path),
             size: metadata.len(),
-            content,
+            truncated: content.text.as_ref().is_some_and(|text| text.truncated),
+            content: content.text.map(|text| text.content),
             extension,
             created_at: Utc::now().to_rfc3339(),
             modified_at: Utc::now().to_rfc3339(),
+            metadata: content.metadata,
+            partial_hash: if self.settings.hash_content { dupes::partial_hash(path).ok() } else { None },
         })
     }
 
-    // Public method for main.rs (used by taskbar progress)
+    /// Indexes a single file with the settings of the root it lies under.
     pub async fn create_file_info_public(&self, path: &Path) -> Result<FileInfo> {
-        let metadata = std::fs::metadata(path)?;
-        let extension = path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown")
-            .to_string();
+        let root = match self.root_for(path)? {
+            Some(root) => root,
+            None => Root::new(path.to_path_buf()),
+        };
+        self.index_file(path, &root).await
+    }
+
+    /// Stores the record for the file at `path` and, when archives are
+    /// enabled and it is one, records for its members.
+    pub async fn index_file(&self, path: &Path, root: &Root) -> Result<FileInfo> {
+        let file_info = self.create_file_info(path, root.content).await?;
+        self.store(&file_info)?;
+        if self.settings.archives {
+            if let Some(kind) = archive::archive_kind(path) {
+                if let Err(e) = self.index_members(path, kind, root) {
+                    warn!(path = %path.display(), error = %e, "cannot read archive");
+                }
+            }
+        }
+        Ok(file_info)
+    }
 
-        let content = if self.is_text_file(&extension) {
-            std::fs::read_to_string(path).ok()
-        } else {
-            None
+    /// Replaces the records for the members of the archive at `path`,
+    /// returning how many were stored.
+    fn index_members(&self, path: &Path, kind: ArchiveKind, root: &Root) -> Result<u64> {
+        self.remove_members(path)?;
+        let mut count = 0;
+        archive::for_each_member(path, kind, |name, size, reader| {
+            if name.split('/').any(|part| self.is_excluded_name(part, Some(root))) {
+                return Ok(());
+            }
+            let member = archive::member_path(path, name);
+            let extension = Path::new(name)
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+            let content = self.read_content(&extension, root.content, || Ok(reader));
+            self.store(&FileInfo {
+                id: file_id(&member),
+                path: member.to_string_lossy().to_string(),
+                path_bytes: models::raw_path_bytes(&member),
+                size,
+                truncated: content.text.as_ref().is_some_and(|text| text.truncated),
+                content: content.text.map(|text| text.content),
+                extension,
+                created_at: Utc::now().to_rfc3339(),
+                modified_at: Utc::now().to_rfc3339(),
+                metadata: content.metadata,
+                partial_hash: None,
+            })?;
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    }
+
+    /// Drops the records for the members of the archive at `path`.
+    fn remove_members(&self, path: &Path) -> Result<u64> {
+        let mut prefix = models::path_bytes(path);
+        prefix.extend_from_slice(archive::SEPARATOR.as_bytes());
+        let mut removed = 0;
+        for item in self.paths.scan_prefix(prefix) {
+            let (key, id) = item?;
+            self.paths.remove(&key)?;
+            if self.db.remove(&id)?.is_some() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Drops the record for the file at `path`, returning whether one existed.
+    pub fn remove_file(&self, path: &Path) -> Result<bool> {
+        self.paths.remove(models::path_bytes(path))?;
+        self.remove_members(path)?;
+        Ok(self.db.remove(file_id(path).as_bytes())?.is_some())
+    }
+
+    /// Drops the records for `path` and, when it is a directory, everything
+    /// below it. Works for paths that no longer exist on disk.
+    ///
+    /// Returns the number of records removed.
+    pub fn remove_path(&self, path: &Path) -> Result<u64> {
+        let path = absolute(path)?;
+        let mut removed = u64::from(self.remove_file(&path)?);
+
+        for item in self.paths.scan_prefix(subtree_prefix(&path)) {
+            let (key, id) = item?;
+            self.paths.remove(&key)?;
+            if self.db.remove(&id)?.is_some() {
+                removed += 1;
+            }
+        }
+        self.db.flush()?;
+        info!(path = %path.display(), files = removed, "removed path");
+        Ok(removed)
+    }
+
+    /// Replaces the records for a file or subtree with fresh ones, leaving
+    /// the rest of the database alone. The path must lie under an indexed
+    /// root. Raising `stop` ends the walk at the next file; files not reached
+    /// by then stay out of the index until the path is re-indexed.
+    ///
+    /// Returns the number of files indexed.
+    pub async fn reindex_path(&self, path: &Path, stop: Option<&AtomicBool>) -> Result<u64> {
+        let path = path.canonicalize()?;
+        let Some(root) = self.root_for(&path)? else {
+            anyhow::bail!("{} is not under an indexed root; use `index` to add it", path.display());
         };
+        self.remove_path(&path)?;
 
-        let file_info = FileInfo {
-            id: format!("{:x}", md5::compute(path.to_string_lossy().as_bytes())),
-            path: path.to_string_lossy().to_string(),
-            size: metadata.len(),
-            content,
-            extension,
-            created_at: Utc::now().to_rfc3339(),
-            modified_at: Utc::now().to_rfc3339(),
+        let roots = self.root_list()?;
+        let mut count = 0;
+        let walker = WalkDir::new(&path)
+            .into_iter()
+            .filter_entry(|e| !self.is_excluded_in(e.path(), &roots))
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file());
+        for entry in walker {
+            if stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                self.db.flush()?;
+                info!(path = %path.display(), files = count, "re-indexing interrupted");
+                return Ok(count);
+            }
+            if self.index_file(entry.path(), &root).await.is_ok() {
+                count += 1;
+            }
+        }
+        self.db.flush()?;
+        info!(path = %path.display(), files = count, "re-indexed path");
+        Ok(count)
+    }
+
+    /// Registers `path` as a root with default settings unless it already
+    /// is one, so files under it may be served. Returns the stored root.
+    pub fn add_root(&self, path: &Path) -> Result<Root> {
+        let canonical = path.canonicalize()?;
+        if let Some(root) = self.root(&canonical)? {
+            return Ok(root);
+        }
+        let mut root = Root::new(canonical);
+        root.name = self.unique_root_name(&root.name)?;
+        self.put_root(&root)?;
+        Ok(root)
+    }
+
+    /// Registers a root or replaces the settings of an existing one, keeping
+    /// its last-indexed time. Names must be unique; an empty name keeps the
+    /// existing root's, or is derived from the directory like [`add_root`].
+    ///
+    /// [`add_root`]: Indexer::add_root
+    pub fn register_root(&self, mut root: Root) -> Result<Root> {
+        root.path = root.path.canonicalize()?;
+        let existing = self.root(&root.path)?;
+        if root.name.is_empty() {
+            root.name = match &existing {
+                Some(existing) => existing.name.clone(),
+                None => self.unique_root_name(&Root::new(root.path.clone()).name)?,
+            };
+        }
+        if let Some(other) = self.find_root(&root.name)? {
+            if other.path != root.path {
+                anyhow::bail!("root name `{}` is already used by {}", root.name, other.path.display());
+            }
+        }
+        root.last_indexed_at = existing.and_then(|r| r.last_indexed_at);
+        self.put_root(&root)?;
+        self.roots.flush()?;
+        Ok(root)
+    }
+
+    /// Unregisters the root with the given name or path and drops its
+    /// records, except those that also lie under another root.
+    ///
+    /// Returns the number of records removed.
+    pub fn remove_root(&self, name_or_path: &str) -> Result<u64> {
+        let Some(root) = self.find_root(name_or_path)? else {
+            anyhow::bail!("no root named `{}`", name_or_path);
         };
+        let key = models::path_bytes(&root.path);
+        self.roots.remove(&key)?;
+        self.checkpoints.remove(&key)?;
 
-        // Store in DB
-        let id = file_info.id.clone();
-        let json = serde_json::to_string(&file_info)?;
-        self.db.insert(id.as_bytes(), json.as_bytes())?;
+        let others = self.root_list()?;
+        let mut removed = 0;
+        for item in self.paths.scan_prefix(subtree_prefix(&root.path)) {
+            let (key, id) = item?;
+            let path = models::path_from_bytes(&key);
+            if others.iter().any(|other| path.starts_with(&other.path)) {
+                continue;
+            }
+            self.paths.remove(&key)?;
+            if self.db.remove(&id)?.is_some() {
+                removed += 1;
+            }
+        }
+        self.db.flush()?;
+        info!(root = %root.name, path = %root.path.display(), files = removed, "removed root");
+        Ok(removed)
+    }
 
-        Ok(file_info)
+    /// Every registered root, ordered by path.
+    pub fn root_list(&self) -> Result<Vec<Root>> {
+        let mut roots = Vec::new();
+        for item in self.roots.iter() {
+            let (key, value) = item?;
+            roots.push(decode_root(&key, &value));
+        }
+        Ok(roots)
+    }
+
+    /// Looks a root up by name, or by path when no name matches.
+    pub fn find_root(&self, name_or_path: &str) -> Result<Option<Root>> {
+        let roots = self.root_list()?;
+        if let Some(root) = roots.iter().find(|r| r.name == name_or_path) {
+            return Ok(Some(root.clone()));
+        }
+        match Path::new(name_or_path).canonicalize() {
+            Ok(path) => Ok(roots.into_iter().find(|r| r.path == path)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The innermost root containing `path`.
+    ///
+    /// Reads every root; when checking many paths, load [`root_list`] once
+    /// and use [`innermost_root`] instead.
+    ///
+    /// [`root_list`]: Indexer::root_list
+    pub fn root_for(&self, path: &Path) -> Result<Option<Root>> {
+        Ok(innermost_root(&self.root_list()?, path).cloned())
+    }
+
+    /// Number of records stored under `path`.
+    pub fn files_under(&self, path: &Path) -> usize {
+        self.paths.scan_prefix(subtree_prefix(path)).count()
+    }
+
+    fn root(&self, canonical: &Path) -> Result<Option<Root>> {
+        let key = models::path_bytes(canonical);
+        Ok(self.roots.get(&key)?.map(|value| decode_root(&key, &value)))
+    }
+
+    fn put_root(&self, root: &Root) -> Result<()> {
+        self.roots.insert(models::path_bytes(&root.path), serde_json::to_vec(root)?)?;
+        Ok(())
+    }
+
+    fn unique_root_name(&self, base: &str) -> Result<String> {
+        let taken: Vec<String> = self.root_list()?.into_iter().map(|r| r.name).collect();
+        let mut name = base.to_string();
+        let mut n = 2;
+        while taken.contains(&name) {
+            name = format!("{}-{}", base, n);
+            n += 1;
+        }
+        Ok(name)
+    }
+
+    /// Records that indexing of the root at `path` has just completed.
+    pub fn mark_indexed(&self, path: &Path) -> Result<()> {
+        let canonical = path.canonicalize()?;
+        let mut root = self.add_root(&canonical)?;
+        root.last_indexed_at = Some(Utc::now().to_rfc3339());
+        self.put_root(&root)?;
+        self.checkpoints.remove(models::path_bytes(&canonical))?;
+        self.roots.flush()?;
+        Ok(())
+    }
+
+    /// Drops records under `root` whose files are gone or now excluded.
+    fn prune(&self, root: &Path) -> Result<u64> {
+        let roots = self.root_list()?;
+        let mut pruned = 0;
+        for item in self.paths.scan_prefix(subtree_prefix(root)) {
+            let (key, _id) = item?;
+            let path = models::path_from_bytes(&key);
+            let exists = match archive::containing_archive(&path) {
+                Some(archive) => self.settings.archives && !self.is_excluded_in(&archive, &roots),
+                None => path.exists(),
+            };
+            if !exists || self.is_excluded_in(&path, &roots) {
+                self.remove_file(&path)?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    /// Returns when the most recent indexing run over any root completed.
+    pub fn last_indexed_at(&self) -> Result<Option<DateTime<Utc>>> {
+        Ok(self
+            .root_list()?
+            .iter()
+            .filter_map(|root| root.last_indexed_at.as_deref())
+            .filter_map(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .max())
+    }
+
+    pub fn metrics(&self) -> &IndexingMetrics {
+        &self.metrics
+    }
+
+    /// Number of files in the index, without decoding any records.
+    pub fn document_count(&self) -> usize {
+        self.db.len()
+    }
+
+    pub fn size_on_disk(&self) -> Result<u64> {
+        Ok(self.db.size_on_disk()?)
+    }
+
+    /// Checks that the database can still be read.
+    pub fn is_ready(&self) -> bool {
+        self.db.first().is_ok()
+    }
+
+    pub fn roots(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.root_list()?.into_iter().map(|root| root.path).collect())
+    }
+
+    /// Checks whether `path` resolves to a location inside an indexed root.
+    ///
+    /// Symlinks and `..` components are resolved first, so a path that merely
+    /// starts with a root string cannot escape it.
+    pub fn is_within_roots(&self, path: &Path) -> Result<bool> {
+        let canonical = match path.canonicalize() {
+            Ok(p) => p,
+            Err(_) => return Ok(false),
+        };
+        Ok(self.roots()?.iter().any(|root| canonical.starts_with(root)))
+    }
+
+    pub fn get_file(&self, id: &str) -> Result<Option<FileInfo>> {
+        match self.db.get(id.as_bytes())? {
+            Some(value) => Ok(Some(record::decode(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a component of `path` below its root matches a configured
+    /// exclude, mirroring what a walk of that root would skip.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.is_excluded_in(path, &self.root_list().unwrap_or_default())
+    }
+
+    /// [`is_excluded`](Indexer::is_excluded) against an already loaded
+    /// [`root_list`](Indexer::root_list), for checking many paths.
+    pub fn is_excluded_in(&self, path: &Path, roots: &[Root]) -> bool {
+        let root = innermost_root(roots, path);
+        let relative = root.and_then(|root| path.strip_prefix(&root.path).ok()).unwrap_or(path);
+        relative
+            .components()
+            .any(|c| self.is_excluded_name(&c.as_os_str().to_string_lossy(), root))
+    }
+
+    /// Checks a file name against the global excludes and those of `root`.
+    fn is_excluded_name(&self, name: &str, root: Option<&Root>) -> bool {
+        self.settings
+            .excludes
+            .iter()
+            .chain(root.into_iter().flat_map(|root| root.excludes.iter()))
+            .any(|pattern| glob_match(pattern, name))
     }
 
     fn is_text_file(&self, extension: &str) -> bool {
-        matches!(
-            extension.to_lowercase().as_str(),
-            "rs" | "py" | "js" | "ts" | "go" | "c" | "cpp" | "h" | "hpp" | "java" | "kt"
-                | "swift" | "rb" | "php" | "scala" | "sh" | "bash" | "yml" | "yaml" | "json"
-                | "toml" | "xml" | "html" | "css" | "sql" | "md" | "txt" | "log"
-        )
+        extension_listed(&self.settings.text_extensions, content::TEXT_EXTENSIONS, extension)
+    }
+
+    fn is_binary_file(&self, extension: &str) -> bool {
+        extension_listed(&self.settings.binary_extensions, content::BINARY_EXTENSIONS, extension)
+    }
+
+    /// Reads the content from `open`: through a registered extractor when
+    /// one matches, otherwise as text up to the content size limit.
+    ///
+    /// The first extractor handling the extension or the MIME type of the
+    /// first bytes is used; those are only sniffed when an extractor that
+    /// matches by MIME type could win. Listed text extensions skip sniffing;
+    /// other files are read only when their first bytes look like text.
+    fn read_content<R: Read>(
+        &self,
+        extension: &str,
+        policy: ContentPolicy,
+        open: impl FnOnce() -> std::io::Result<R>,
+    ) -> Content {
+        if policy == ContentPolicy::NamesOnly {
+            return Content::default();
+        }
+        let limit = self
+            .settings
+            .max_content_size
+            .unwrap_or(content::DEFAULT_MAX_CONTENT_SIZE)
+            .min(record::MAX_CONTENT_LEN);
+        let by_mime = extract::needs_mime(&self.extractors, extension);
+        if !by_mime {
+            if let Some(extractor) = extract::find(&self.extractors, extension, None) {
+                return open().map_or_else(|_| Content::default(), |reader| self.extract(extractor, reader, limit));
+            }
+        }
+
+        let binary = policy == ContentPolicy::Text && self.is_binary_file(extension);
+        if binary && !by_mime {
+            return Content::default();
+        }
+        let Ok(mut reader) = open() else {
+            return Content::default();
+        };
+        let mut head = Vec::with_capacity(content::SNIFF_LEN);
+        if (&mut reader).take(content::SNIFF_LEN as u64).read_to_end(&mut head).is_err() {
+            return Content::default();
+        }
+        if by_mime {
+            let mime = infer::get(&head).map(|kind| kind.mime_type());
+            if let Some(extractor) = extract::find(&self.extractors, extension, mime) {
+                return self.extract(extractor, Cursor::new(head).chain(reader), limit);
+            }
+        }
+        if binary {
+            return Content::default();
+        }
+
+        let sniff = policy == ContentPolicy::All || !self.is_text_file(extension);
+        let text = content::read_text(Cursor::new(head).chain(reader), limit, sniff, self.fallback_encoding)
+            .ok()
+            .flatten();
+        Content { text, metadata: BTreeMap::new() }
+    }
+
+    fn extract(&self, extractor: &dyn Extractor, reader: impl Read, limit: u64) -> Content {
+        let mut bytes = Vec::new();
+        if reader.take(extract::MAX_DOCUMENT_SIZE + 1).read_to_end(&mut bytes).is_err()
+            || bytes.len() as u64 > extract::MAX_DOCUMENT_SIZE
+        {
+            return Content::default();
+        }
+        match extractor.extract(&bytes) {
+            Ok(extracted) => Content {
+                text: (!extracted.text.is_empty()).then(|| content::Text::capped(extracted.text, limit)),
+                metadata: extracted.metadata,
+            },
+            Err(e) => {
+                debug!(extractor = extractor.name(), error = %e, "extraction failed");
+                Content::default()
+            }
+        }
+    }
+
+    /// The encoded records of every file, or only of those below `prefix`.
+    fn records(&self, prefix: Option<&Path>) -> Result<Box<dyn Iterator<Item = sled::Result<sled::IVec>> + '_>> {
+        // A subtree is a contiguous key range of the path tree, so scoped
+        // scans only decode the records below the prefix.
+        Ok(match prefix {
+            Some(prefix) => {
+                let prefix = subtree_prefix(&absolute(prefix)?);
+                Box::new(self.paths.scan_prefix(prefix).filter_map(|item| match item {
+                    Ok((_path, id)) => self.db.get(id).transpose(),
+                    Err(e) => Some(Err(e)),
+                }))
+            }
+            None => Box::new(self.db.iter().values()),
+        })
+    }
+
+    /// Finds groups of indexed files with identical content; see
+    /// [`dupes::find_duplicates`]. Empty files and archive members are
+    /// never reported.
+    pub fn duplicates(&self, filter: &DuplicateFilter) -> Result<DuplicateReport> {
+        let mut files = Vec::new();
+        for value in self.records(filter.path_prefix.as_deref())? {
+            let file_info = record::decode_without_content(&value?)?;
+            if file_info.size > 0 && file_info.size >= filter.min_size {
+                files.push(file_info);
+            }
+        }
+        Ok(dupes::find_duplicates(files))
     }
 
     pub fn search(&self, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+        let never = AtomicBool::new(false);
+        Ok(self.search_with_cancel(filter, &never)?.unwrap_or_default())
+    }
+
+    /// Runs a search that can be abandoned midway.
+    ///
+    /// The `cancel` flag is polled while scanning the database; once it is set
+    /// the scan stops and `Ok(None)` is returned, so a superseded query does
+    /// not keep walking every record.
+    pub fn search_with_cancel(
+        &self,
+        filter: &SearchFilter,
+        cancel: &AtomicBool,
+    ) -> Result<Option<Vec<SearchResult>>> {
         let mut results = Vec::new();
+        let (query, mut conditions) =
+            models::split_metadata_terms(&filter.query, &extract::fields(&self.extractors));
+        conditions.extend(filter.metadata.iter().cloned());
 
-        for item in self.db.iter() {
-            let (_key, value) = item?;
-            let file_info: FileInfo = serde_json::from_slice(&value)?;
+        for (scanned, value) in self.records(filter.path_prefix.as_deref())?.enumerate() {
+            if scanned % CANCEL_CHECK_INTERVAL == 0 && cancel.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+
+            // Content is only decompressed for content searches and for the
+            // preview of a hit.
+            let value = value?;
+            let mut file_info = if filter.search_content {
+                record::decode(&value)?
+            } else {
+                record::decode_without_content(&value)?
+            };
 
             // Check filters
             if !filter.extensions.is_empty()
@@ -131,22 +868,38 @@ impl Indexer {
                 continue;
             }
 
+            if !conditions.iter().all(|condition| condition.matches(&file_info.metadata)) {
+                continue;
+            }
+
             // Search
             let score = if filter.search_content {
                 self.engine
-                    .search_content(&file_info, &filter.query, filter.case_sensitive)
+                    .search_content(&file_info, &query, filter.case_sensitive)
+            } else if filter.fuzzy {
+                self.engine
+                    .search_fuzzy(&file_info, &query, filter.case_sensitive)
             } else {
                 self.engine
-                    .search_filename(&file_info, &filter.query, filter.case_sensitive)
+                    .search_filename(&file_info, &query, filter.case_sensitive)
             };
 
             if score > 0.0 {
-                let matched_content = self.engine
-                    .extract_preview(&file_info, &filter.query, filter.case_sensitive);
+                let matched_content = if query.is_empty() {
+                    None
+                } else {
+                    if !filter.search_content {
+                        file_info = record::decode(&value)?;
+                    }
+                    self.engine.extract_preview(&file_info, &query, filter.case_sensitive)
+                };
 
                 results.push(SearchResult {
                     file_id: file_info.id,
                     path: file_info.path,
+                    path_bytes: file_info.path_bytes,
+                    truncated: file_info.truncated,
+                    metadata: file_info.metadata,
                     score,
                     size: file_info.size,
                     matched_content,
@@ -160,7 +913,11 @@ impl Indexer {
         results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
         results.truncate(filter.limit);
 
-        Ok(results)
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        Ok(Some(results))
     }
 
     pub fn get_stats(&self) -> Result<DatabaseStats> {
@@ -170,7 +927,7 @@ impl Indexer {
 
         for item in self.db.iter() {
             let (_key, value) = item?;
-            let file_info: FileInfo = serde_json::from_slice(&value)?;
+            let file_info = record::decode_without_content(&value)?;
 
             *extensions.entry(file_info.extension).or_insert(0) += 1;
             total_size += file_info.size;
@@ -182,6 +939,7 @@ impl Indexer {
             total_size: self.format_size(total_size),
             indexed_at: Utc::now().to_rfc3339(),
             extensions,
+            schema_version: schema::stored_version(&self.db)?.unwrap_or(0),
         })
     }
 
@@ -200,14 +958,17 @@ impl Indexer {
 
     pub fn vacuum(&self) -> Result<()> {
         self.db.flush()?;
-        println!("✅ Database optimized");
+        info!("database optimized");
         Ok(())
     }
 
     pub fn clear(&self) -> Result<()> {
         self.db.clear()?;
+        self.paths.clear()?;
+        self.roots.clear()?;
+        self.checkpoints.clear()?;
         self.db.flush()?;
-        println!("✅ Database cleared");
+        info!("database cleared");
         Ok(())
     }
 }
@@ -215,6 +976,7 @@ impl Indexer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{content_filter, TempDir};
 
     #[tokio::test]
     async fn test_is_text_file() {
@@ -223,4 +985,676 @@ mod tests {
         assert!(indexer.is_text_file("py"));
         assert!(!indexer.is_text_file("png"));
     }
+
+    #[tokio::test]
+    async fn test_search_with_cancel() {
+        let dir = TempDir::new("cancel");
+        let file = dir.join("main.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.create_file_info_public(&file).await.unwrap();
+
+        let filter = SearchFilter {
+            query: "main".to_string(),
+            max_size: u64::MAX,
+            limit: 10,
+            ..Default::default()
+        };
+
+        let live = AtomicBool::new(false);
+        let results = indexer.search_with_cancel(&filter, &live).unwrap();
+        assert_eq!(results.map(|r| r.len()), Some(1));
+
+        let cancelled = AtomicBool::new(true);
+        assert!(indexer.search_with_cancel(&filter, &cancelled).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        let dir = TempDir::new("resume");
+        let root = dir.join("root");
+        for sub in ["a", "b", "c"] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+            for name in ["1.txt", "2.txt"] {
+                std::fs::write(root.join(sub).join(name), sub).unwrap();
+            }
+        }
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        let stop = AtomicBool::new(false);
+        let stop_after_three = |count: u64| {
+            if count == 3 {
+                stop.store(true, Ordering::Relaxed);
+            }
+        };
+        let interrupted = indexer
+            .index_dir_with(&root, &IndexOptions {
+                stop: Some(&stop),
+                progress: Some(&stop_after_three),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(!interrupted.completed);
+        assert_eq!(interrupted.files, 3);
+
+        let checkpoint = indexer.checkpoint(&root).unwrap().unwrap();
+        assert!(checkpoint.last_path.ends_with("b/1.txt"));
+        assert_eq!(checkpoint.files_indexed, 3);
+
+        let resumed = indexer
+            .index_dir_with(&root, &IndexOptions { resume: true, ..Default::default() })
+            .await
+            .unwrap();
+        assert!(resumed.completed);
+        assert_eq!(resumed.files, 3);
+        assert_eq!(indexer.document_count(), 6);
+        assert!(indexer.checkpoint(&root).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_is_within_roots() {
+        let dir = TempDir::new("roots");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("inside.txt"), "inside").unwrap();
+        std::fs::write(dir.join("outside.txt"), "outside").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        assert!(!indexer.is_within_roots(&root.join("inside.txt")).unwrap());
+
+        indexer.add_root(&root).unwrap();
+        assert!(indexer.is_within_roots(&root.join("inside.txt")).unwrap());
+        assert!(!indexer.is_within_roots(&dir.join("outside.txt")).unwrap());
+        assert!(!indexer.is_within_roots(&root.join("../outside.txt")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_index_settings() {
+        let dir = TempDir::new("settings");
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("notes.org"), "settings needle").unwrap();
+        std::fs::write(root.join("main.rs"), "settings needle").unwrap();
+        std::fs::write(root.join("debug.log"), "settings needle").unwrap();
+        std::fs::write(root.join("target").join("out.org"), "settings needle").unwrap();
+
+        let settings = IndexSettings {
+            excludes: vec!["target".to_string(), "*.log".to_string()],
+            text_extensions: vec!["org".to_string()],
+            binary_extensions: vec!["rs".to_string()],
+            ..Default::default()
+        };
+        let indexer = Indexer::with_settings(&dir.db(), settings).unwrap();
+        assert_eq!(indexer.index_dir(&root).await.unwrap(), 2);
+
+        let filter = content_filter("needle");
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("notes.org"));
+        let root = root.canonicalize().unwrap();
+        assert!(indexer.is_excluded(&root.join("target").join("x.rs")));
+        assert!(!indexer.is_excluded(&root.join("src").join("x.rs")));
+    }
+
+    #[tokio::test]
+    async fn test_content_sniffing() {
+        let dir = TempDir::new("sniff");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("Makefile"), "build:\n\techo sniffneedle\n").unwrap();
+        std::fs::write(root.join("deploy"), "#!/bin/sh\necho sniffneedle\n").unwrap();
+        std::fs::write(root.join("notes.ini"), b"[caf\xe9]\nkey=sniffneedle\n").unwrap();
+        std::fs::write(root.join("wide.txt"), b"\xff\xfes\0n\0i\0f\0f\0n\0e\0e\0d\0l\0e\0").unwrap();
+        std::fs::write(root.join("image.dat"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR sniffneedle").unwrap();
+        std::fs::write(root.join("photo.png"), "sniffneedle").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+
+        let filter = content_filter("sniffneedle");
+        let mut names: Vec<String> = indexer
+            .search(&filter)
+            .unwrap()
+            .iter()
+            .map(|r| Path::new(&r.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Makefile", "deploy", "notes.ini", "wide.txt"]);
+
+        let ini = indexer.get_file(&file_id(&root.canonicalize().unwrap().join("notes.ini"))).unwrap().unwrap();
+        assert!(ini.content.unwrap().starts_with("[café]"));
+
+        assert!(Indexer::with_settings(
+            dir.join("db2").to_str().unwrap(),
+            IndexSettings { fallback_encoding: Some("klingon".to_string()), ..Default::default() },
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_max_content_size() {
+        let dir = TempDir::new("cap");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        let big = format!("early {} late", "filler ".repeat(50_000));
+        std::fs::write(root.join("big.log"), &big).unwrap();
+        std::fs::write(root.join("small.log"), "early late").unwrap();
+
+        let settings = IndexSettings { max_content_size: Some(1024), ..Default::default() };
+        let indexer = Indexer::with_settings(&dir.db(), settings).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+
+        let mut filter = content_filter("early");
+        let mut results = indexer.search(&filter).unwrap();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(results.len(), 2);
+        assert!(results[0].truncated);
+        assert!(!results[1].truncated);
+        assert_eq!(results[0].size, big.len() as u64);
+
+        filter.query = "late".to_string();
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("small.log"));
+    }
+
+    #[tokio::test]
+    async fn test_archive_members() {
+        use std::io::Write;
+
+        let dir = TempDir::new("archive");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(root.join("bundle.zip")).unwrap());
+        zip.start_file("src/lib.rs", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"pub fn zipneedle() {}").unwrap();
+        zip.finish().unwrap();
+
+        let gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(root.join("src.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(14);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "docs/notes.md", &b"tar tarneedle\n"[..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let mut log = flate2::write::GzEncoder::new(
+            std::fs::File::create(root.join("app.log.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        log.write_all(b"error gzneedle\n").unwrap();
+        log.finish().unwrap();
+        std::fs::write(root.join("trace.txt.zst"), zstd::encode_all(&b"zstneedle"[..], 0).unwrap()).unwrap();
+
+        let settings = IndexSettings { archives: true, ..Default::default() };
+        let indexer = Indexer::with_settings(&dir.db(), settings).unwrap();
+        assert_eq!(indexer.index_dir(&root).await.unwrap(), 4);
+
+        let search = |query: &str| {
+            let filter = content_filter(query);
+            indexer.search(&filter).unwrap().into_iter().map(|r| r.path).collect::<Vec<_>>()
+        };
+        let root = root.canonicalize().unwrap();
+        let zip_member = format!("{}!/src/lib.rs", root.join("bundle.zip").display());
+        assert_eq!(search("zipneedle"), std::slice::from_ref(&zip_member));
+        assert_eq!(search("tarneedle"), [format!("{}!/docs/notes.md", root.join("src.tar.gz").display())]);
+        assert_eq!(search("gzneedle"), [root.join("app.log.gz").display().to_string()]);
+        assert_eq!(search("zstneedle"), [root.join("trace.txt.zst").display().to_string()]);
+
+        // Members survive pruning on a second run and go with their archive.
+        indexer.index_dir(&root).await.unwrap();
+        assert_eq!(indexer.get_stats().unwrap().total_files, 6);
+        assert_eq!(archive::on_disk(Path::new(&zip_member)), root.join("bundle.zip"));
+        std::fs::remove_file(root.join("bundle.zip")).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+        assert!(search("zipneedle").is_empty());
+        assert_eq!(indexer.remove_path(&root.join("src.tar.gz")).unwrap(), 1);
+        assert!(search("tarneedle").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_document_text() {
+        use std::io::Write;
+
+        let dir = TempDir::new("docs");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        let mut docx = zip::ZipWriter::new(std::fs::File::create(root.join("Report.docx")).unwrap());
+        docx.start_file("word/document.xml", zip::write::SimpleFileOptions::default()).unwrap();
+        docx.write_all(b"<w:document><w:body><w:p><w:r><w:t>Budget docneedle \xc3\xa9t\xc3\xa9 summary</w:t></w:r></w:p></w:body></w:document>").unwrap();
+        docx.finish().unwrap();
+        std::fs::write(root.join("broken.pdf"), "%PDF-1.4 docneedle").unwrap();
+
+        let settings = IndexSettings { max_content_size: Some(19), ..Default::default() };
+        let indexer = Indexer::with_settings(&dir.db(), settings).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+
+        let filter = content_filter("docneedle");
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("Report.docx"));
+        assert!(results[0].truncated);
+        let record = indexer.get_file(&results[0].file_id).unwrap().unwrap();
+        assert_eq!(record.content.unwrap(), "Budget docneedle \u{e9}");
+    }
+
+    #[tokio::test]
+    async fn test_metadata_queries() {
+        fn png(width: u32, height: u32) -> Vec<u8> {
+            let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+            bytes.extend(width.to_be_bytes());
+            bytes.extend(height.to_be_bytes());
+            bytes.extend([8, 2, 0, 0, 0, 0, 0, 0, 0]);
+            bytes
+        }
+
+        let dir = TempDir::new("media");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("panorama.png"), png(6000, 2000)).unwrap();
+        std::fs::write(root.join("icon.png"), png(64, 64)).unwrap();
+        let mut tag = id3::Tag::new();
+        id3::TagLike::set_artist(&mut tag, "Nina Simone");
+        let mut mp3 = Vec::new();
+        tag.write_to(&mut mp3, id3::Version::Id3v24).unwrap();
+        std::fs::write(root.join("sinnerman.mp3"), mp3).unwrap();
+        std::fs::write(root.join("read.rs"), "use std::io::Read;\nlet url = \"key:value\";").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+
+        let search = |query: &str| {
+            let filter = SearchFilter {
+                query: query.to_string(),
+                max_size: u64::MAX,
+                limit: 10,
+                ..Default::default()
+            };
+            let mut paths: Vec<String> = indexer.search(&filter).unwrap().into_iter().map(|r| r.path).collect();
+            paths.sort();
+            paths
+        };
+        let wide = search("width:>3000");
+        assert_eq!(wide.len(), 1);
+        assert!(wide[0].ends_with("panorama.png"));
+        assert_eq!(search("png width:<=64 height:64").len(), 1);
+        assert_eq!(search("icon width:>3000").len(), 0);
+        assert_eq!(search("artist:\"nina simone\"").len(), 1);
+        assert_eq!(search("artist:=nina").len(), 0);
+
+        // Words with colons are only filters on known fields.
+        for query in ["std::io::Read", "std::io", "key:value"] {
+            let results = indexer.search(&content_filter(query)).unwrap();
+            assert_eq!(results.len(), 1, "{}", query);
+            assert!(results[0].path.ends_with("read.rs"));
+        }
+
+        let filter = SearchFilter {
+            query: "png".to_string(),
+            max_size: u64::MAX,
+            limit: 10,
+            metadata: vec!["height:2000".parse().unwrap()],
+            ..Default::default()
+        };
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].metadata.get("width").map(String::as_str), Some("6000"));
+    }
+
+    #[tokio::test]
+    async fn test_duplicates() {
+        let dir = TempDir::new("dupes");
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("backup")).unwrap();
+        let big = vec![7u8; 100_000];
+        std::fs::write(root.join("photo.raw"), &big).unwrap();
+        std::fs::write(root.join("backup/photo.raw"), &big).unwrap();
+        std::fs::write(root.join("backup/photo copy.raw"), &big).unwrap();
+        let mut changed = big.clone();
+        changed[50_000] = 8;
+        std::fs::write(root.join("edited.raw"), &changed).unwrap();
+        std::fs::write(root.join("a.txt"), "same").unwrap();
+        std::fs::write(root.join("backup/a.txt"), "same").unwrap();
+        std::fs::write(root.join("empty1"), "").unwrap();
+        std::fs::write(root.join("empty2"), "").unwrap();
+
+        let settings = IndexSettings { hash_content: true, ..Default::default() };
+        let indexer = Indexer::with_settings(&dir.db(), settings).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+        let record = indexer.get_file(&file_id(&root.join("edited.raw"))).unwrap().unwrap();
+        assert!(record.partial_hash.is_some());
+
+        let report = indexer.duplicates(&DuplicateFilter::default()).unwrap();
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.duplicate_files, 3);
+        assert_eq!(report.wasted, 200_004);
+        let photos = &report.groups[0];
+        assert_eq!(photos.files.len(), 3);
+        assert!(photos.files.iter().all(|file| file.path.ends_with("photo.raw") || file.path.ends_with("photo copy.raw")));
+
+        let filter = DuplicateFilter { min_size: 10, ..Default::default() };
+        assert_eq!(indexer.duplicates(&filter).unwrap().groups.len(), 1);
+        let filter = DuplicateFilter { path_prefix: Some(root.join("backup")), ..Default::default() };
+        let report = indexer.duplicates(&filter).unwrap();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].files.len(), 2);
+
+        // An edit that keeps the size leaves the stored hash stale.
+        std::fs::write(root.join("a.txt"), "diff").unwrap();
+        let report = indexer.duplicates(&DuplicateFilter::default()).unwrap();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].files.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_custom_extractor() {
+        struct Sketch;
+
+        impl Extractor for Sketch {
+            fn name(&self) -> &str {
+                "sketch"
+            }
+
+            fn mime_types(&self) -> &[&str] {
+                &["image/png", "application/pdf"]
+            }
+
+            fn fields(&self) -> &[&str] {
+                &["tool"]
+            }
+
+            fn extract(&self, bytes: &[u8]) -> Result<crate::Extracted> {
+                let mut extracted = crate::Extracted::text(format!("sketch of {} bytes", bytes.len()));
+                extracted.metadata.insert("tool".to_string(), "sketchpad".to_string());
+                Ok(extracted)
+            }
+        }
+
+        let dir = TempDir::new("extractor");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("drawing.bin"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        std::fs::write(root.join("notes.txt"), "sketch of plans").unwrap();
+        // Registered extractors win over the built-in PDF one.
+        std::fs::write(root.join("scan.pdf"), b"%PDF-1.4 broken").unwrap();
+
+        let mut indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.register_extractor(Sketch);
+        indexer.index_dir(&root).await.unwrap();
+
+        let filter = content_filter("sketch of");
+        let mut results = indexer.search(&filter).unwrap();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(results.len(), 3);
+        assert!(results[0].path.ends_with("drawing.bin"));
+        assert_eq!(results[0].metadata.get("tool").map(String::as_str), Some("sketchpad"));
+        assert!(results[1].metadata.is_empty());
+        assert!(results[2].path.ends_with("scan.pdf"));
+        assert_eq!(results[2].metadata.get("tool").map(String::as_str), Some("sketchpad"));
+        assert_eq!(indexer.search(&content_filter("tool:sketchpad")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "debug.log"));
+        assert!(glob_match("node_modules", "node_modules"));
+        assert!(glob_match("cache-?", "cache-1"));
+        assert!(!glob_match("*.log", "debug.txt"));
+        assert!(!glob_match("cache-?", "cache-12"));
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_reindex_path() {
+        let dir = TempDir::new("remove");
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::create_dir_all(root.join("subling")).unwrap();
+        std::fs::write(root.join("sub").join("a.txt"), "alpha").unwrap();
+        std::fs::write(root.join("sub").join("b.txt"), "beta").unwrap();
+        std::fs::write(root.join("subling").join("c.txt"), "gamma").unwrap();
+        std::fs::write(root.join("top.txt"), "top").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        assert_eq!(indexer.index_dir(&root).await.unwrap(), 4);
+
+        assert_eq!(indexer.remove_path(&root.join("sub")).unwrap(), 2);
+        assert_eq!(indexer.document_count(), 2);
+        assert_eq!(indexer.remove_path(&root.join("missing")).unwrap(), 0);
+
+        std::fs::remove_file(root.join("sub").join("b.txt")).unwrap();
+        std::fs::write(root.join("sub").join("d.txt"), "delta").unwrap();
+        let stop = AtomicBool::new(true);
+        assert_eq!(indexer.reindex_path(&root.join("sub"), Some(&stop)).await.unwrap(), 0);
+        assert_eq!(indexer.document_count(), 2);
+        assert_eq!(indexer.reindex_path(&root.join("sub"), None).await.unwrap(), 2);
+        assert_eq!(indexer.document_count(), 4);
+
+        let filter = SearchFilter {
+            query: ".txt".to_string(),
+            max_size: u64::MAX,
+            limit: 10,
+            ..Default::default()
+        };
+        let mut names: Vec<String> = indexer
+            .search(&filter)
+            .unwrap()
+            .iter()
+            .map(|r| Path::new(&r.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["a.txt", "c.txt", "d.txt", "top.txt"]);
+
+        assert!(indexer.reindex_path(&dir, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_named_roots() {
+        let dir = TempDir::new("named");
+        let docs = dir.join("docs");
+        let code = dir.join("code");
+        std::fs::create_dir_all(docs.join("drafts")).unwrap();
+        std::fs::create_dir_all(&code).unwrap();
+        std::fs::write(docs.join("plan.txt"), "roots needle").unwrap();
+        std::fs::write(docs.join("drafts").join("old.txt"), "roots needle").unwrap();
+        std::fs::write(code.join("lib.rs"), "roots needle").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        let mut root = Root::new(docs.clone());
+        root.excludes = vec!["drafts".to_string()];
+        root.content = ContentPolicy::NamesOnly;
+        let docs_root = indexer.register_root(root).unwrap();
+        assert_eq!(docs_root.name, "docs");
+
+        let mut clash = Root::new(code.clone());
+        clash.name = "docs".to_string();
+        assert!(indexer.register_root(clash).is_err());
+
+        // Without a name, a second `docs` directory gets a unique one and a
+        // registered root keeps its own.
+        let other = dir.join("other").join("docs");
+        std::fs::create_dir_all(&other).unwrap();
+        let unnamed = Root { name: String::new(), ..Root::new(other) };
+        assert_eq!(indexer.register_root(unnamed).unwrap().name, "docs-2");
+        let unnamed = Root { name: String::new(), ..docs_root.clone() };
+        assert_eq!(indexer.register_root(unnamed).unwrap().name, "docs");
+        indexer.remove_root("docs-2").unwrap();
+
+        assert_eq!(indexer.index_dir(&docs).await.unwrap(), 1);
+        assert_eq!(indexer.index_dir(&code).await.unwrap(), 1);
+        let roots = indexer.root_list().unwrap();
+        assert_eq!(roots.len(), 2);
+        assert!(roots.iter().all(|r| r.last_indexed_at.is_some()));
+
+        // Only the code root indexes content.
+        let filter = content_filter("needle");
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("lib.rs"));
+
+        // A later run drops files that disappeared.
+        std::fs::remove_file(docs.join("plan.txt")).unwrap();
+        indexer.index_dir(&docs).await.unwrap();
+        assert_eq!(indexer.document_count(), 1);
+
+        assert_eq!(indexer.remove_root("code").unwrap(), 1);
+        assert_eq!(indexer.document_count(), 0);
+        assert!(indexer.find_root("code").unwrap().is_none());
+        assert!(indexer.remove_root("code").is_err());
+    }
+
+    #[test]
+    fn test_decode_legacy_root() {
+        let root = decode_root(b"/srv/data", b"2024-01-19T00:00:00+00:00");
+        assert_eq!(root.name, "data");
+        assert_eq!(root.path, PathBuf::from("/srv/data"));
+        assert_eq!(root.content, ContentPolicy::Text);
+        assert_eq!(root.last_indexed_at.as_deref(), Some("2024-01-19T00:00:00+00:00"));
+        assert!(decode_root(b"/srv/data", b"").last_indexed_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_path_prefix() {
+        let dir = TempDir::new("prefix");
+        let root = dir.join("root");
+        std::fs::create_dir_all(root.join("app").join("src")).unwrap();
+        std::fs::create_dir_all(root.join("apple")).unwrap();
+        std::fs::write(root.join("app").join("src").join("main.rs"), "").unwrap();
+        std::fs::write(root.join("apple").join("main.rs"), "").unwrap();
+        std::fs::write(root.join("main.rs"), "").unwrap();
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+
+        let mut filter = SearchFilter {
+            query: "main".to_string(),
+            max_size: u64::MAX,
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(indexer.search(&filter).unwrap().len(), 3);
+
+        filter.path_prefix = Some(root.join("app"));
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].path.ends_with("src/main.rs"));
+
+        filter.path_prefix = Some(root.join("missing"));
+        assert!(indexer.search(&filter).unwrap().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new("raw");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        // Both names decode to the same lossy string.
+        let names: [&[u8]; 2] = [b"bad\xffname.txt", b"bad\xfename.txt"];
+        for name in names {
+            std::fs::write(root.join(OsStr::from_bytes(name)), "needle").unwrap();
+        }
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        indexer.index_dir(&root).await.unwrap();
+        assert_eq!(indexer.get_stats().unwrap().total_files, 2);
+
+        let filter = SearchFilter { path_prefix: Some(root.clone()), ..content_filter("needle") };
+        let results = indexer.search(&filter).unwrap();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            let path = result.os_path();
+            assert!(path.exists());
+            assert!(names.contains(&path.file_name().unwrap().as_bytes()));
+            assert_eq!(result.path_bytes.as_deref(), Some(path.as_os_str().as_bytes()));
+        }
+
+        assert_eq!(indexer.remove_path(&root.join(OsStr::from_bytes(names[0]))).unwrap(), 1);
+        assert_eq!(indexer.search(&filter).unwrap().len(), 1);
+    }
+
+    /// Storage benchmark behind the numbers for the binary record layout.
+    /// It indexes a generated corpus, then copies the records into two fresh
+    /// databases, one as stored and one re-encoded as the JSON of earlier
+    /// versions, and compares their size and search times:
+    ///
+    /// ```text
+    /// cargo test --release bench_record_encoding -- --ignored --nocapture
+    /// ```
+    #[tokio::test]
+    #[ignore]
+    async fn bench_record_encoding() {
+        use std::time::{Duration, Instant};
+
+        const FILES: usize = 4000;
+        const WORDS: &[&str] = &[
+            "fn", "let", "mut", "impl", "struct", "pub", "use", "match", "Some", "None",
+            "Result", "Ok", "Err", "self", "return", "where", "String", "Vec", "usize", "iter",
+        ];
+
+        let dir = TempDir::new("bench");
+        let root = dir.join("root");
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for i in 0..FILES {
+            let sub = root.join(format!("crate{}", i % 40));
+            std::fs::create_dir_all(&sub).unwrap();
+            let mut text = String::new();
+            // xorshift keeps the corpus identical between runs.
+            for _ in 0..200 + i % 1800 {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                text.push_str(WORDS[seed as usize % WORDS.len()]);
+                text.push(if seed.is_multiple_of(11) { '\n' } else { ' ' });
+            }
+            std::fs::write(sub.join(format!("lib{}.rs", i)), text).unwrap();
+        }
+
+        let indexer = Indexer::new(&dir.db()).unwrap();
+        let started = Instant::now();
+        indexer.index_dir(&root).await.unwrap();
+        println!("indexing {} files: {:?}", FILES, started.elapsed());
+
+        let best_of = |indexer: &Indexer, filter: &SearchFilter| {
+            (0..5)
+                .map(|_| {
+                    let started = Instant::now();
+                    indexer.search(filter).unwrap();
+                    started.elapsed()
+                })
+                .min()
+                .unwrap_or(Duration::ZERO)
+        };
+        let names = SearchFilter { limit: usize::MAX, ..Default::default() };
+        let searches = [
+            ("name search \"lib1\"", SearchFilter { query: "lib1".to_string(), ..names.clone() }),
+            ("name search, no hits", SearchFilter { query: "nothing".to_string(), ..names }),
+            ("content search \"where\"", SearchFilter { limit: usize::MAX, ..content_filter("where") }),
+            ("content search, no hits", content_filter("nothing")),
+        ];
+
+        for (encoding, json) in [("binary", false), ("json", true)] {
+            let copy = Indexer::new(&dir.join(encoding).to_string_lossy()).unwrap();
+            for value in indexer.db.iter().values() {
+                let value = value.unwrap();
+                let file_info = record::decode(&value).unwrap();
+                let stored = if json { serde_json::to_vec(&file_info).unwrap() } else { value.to_vec() };
+                copy.db.insert(file_info.id.as_bytes(), stored).unwrap();
+            }
+            copy.db.flush().unwrap();
+            println!("{}: database size {} bytes", encoding, copy.size_on_disk().unwrap());
+            for (name, filter) in &searches {
+                println!("{}: {} {:?}", encoding, name, best_of(&copy, filter));
+            }
+        }
+    }
 }
@@ -1,7 +1,31 @@
 pub mod models;
 pub mod indexer;
 pub mod search_engine;
+pub mod metrics;
+pub mod logging;
+pub mod shutdown;
+pub mod output;
+pub mod config;
+pub mod content;
+pub mod archive;
+pub mod extract;
+pub mod media;
+pub mod dupes;
+pub mod record;
+pub mod schema;
+pub mod actions;
+#[cfg(all(unix, feature = "daemon"))]
+pub mod daemon;
+#[cfg(feature = "server")]
+pub mod auth;
+#[cfg(feature = "server")]
+pub mod web_server;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(test)]
+mod testing;
 
-pub use models::{SearchResult, SearchFilter, FileInfo, DatabaseStats};
-pub use indexer::Indexer;
+pub use models::{SearchResult, SearchFilter, MetadataFilter, Comparison, FileInfo, DatabaseStats, Checkpoint, IndexSettings, Root, ContentPolicy, DuplicateFilter, DuplicateGroup, DuplicateReport};
+pub use indexer::{Indexer, IndexOptions, IndexOutcome};
 pub use search_engine::SearchEngine;
+pub use extract::{Extracted, Extractor};
@@ -1,8 +1,15 @@
-use rust_search::{Indexer, SearchFilter};
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
-use anyhow::Result;
+use rust_search::{ContentPolicy, DatabaseStats, DuplicateFilter, DuplicateReport, IndexOptions, Indexer, MetadataFilter, Root, SearchFilter, SearchResult};
+use rust_search::actions;
+use rust_search::config::Config;
+use rust_search::output::{self, OutputFormat};
+use rust_search::shutdown;
+use rust_search::logging::{self, LogFormat};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use clap::{CommandFactory, Parser, Subcommand};
+use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use tracing::{info, warn};
 
 #[derive(Parser)]
 #[command(name = "rust-search")]
@@ -12,23 +19,39 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Search query or index path
-    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-    args: Vec<String>,
+    /// Log output format (text or json); levels are set with RUST_LOG
+    #[arg(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Database path [default: from config, RUST_SEARCH_DB, or the XDG data dir]
+    #[arg(long, global = true)]
+    db: Option<String>,
+
+    /// Config file [default: $XDG_CONFIG_HOME/rust-search/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Index directory
+    /// Index a directory, or every configured root when none is given
     Index {
-        path: PathBuf,
-        #[arg(long, default_value = "results.db")]
-        db: String,
+        path: Option<PathBuf>,
+        /// Continue an interrupted run from its last checkpoint
+        #[arg(long)]
+        resume: bool,
+        /// Also index the members of zip and tar archives
+        #[arg(long)]
+        archives: bool,
+        /// Store partial content hashes to speed up `dupes`
+        #[arg(long)]
+        hash: bool,
     },
 
     /// Search indexed files
     Search {
-        query: String,
+        #[arg(required = true)]
+        query: Vec<String>,
         #[arg(long)]
         all: bool,
         #[arg(long)]
@@ -37,50 +60,177 @@ enum Commands {
         min_size: Option<u64>,
         #[arg(long)]
         max_size: Option<u64>,
-        #[arg(long)]
+        #[arg(short = 'n', long)]
         limit: Option<usize>,
         #[arg(long)]
         case_sensitive: bool,
-        #[arg(long, default_value = "results.db")]
-        db: String,
+        /// Output format: text, json, ndjson, csv, tsv or paths
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+        /// Print bare paths separated by NUL bytes (for xargs -0)
+        #[arg(short = '0', long)]
+        print0: bool,
+        /// Open result N: content hits in $EDITOR at the match, others with the default app
+        #[arg(long, value_name = "N", conflicts_with_all = ["exec", "first"])]
+        open: Option<usize>,
+        /// Run a shell command for each result; `{}` is replaced by the path
+        #[arg(long, value_name = "CMD")]
+        exec: Option<String>,
+        /// Keep only the best result and print its path
+        #[arg(long)]
+        first: bool,
+        /// Only return files below this directory
+        #[arg(long = "in", value_name = "DIR", conflicts_with = "here")]
+        in_dir: Option<PathBuf>,
+        /// Only return files below the current directory
+        #[arg(long)]
+        here: bool,
+    },
+
+    /// Manage the registered index roots
+    Roots {
+        #[command(subcommand)]
+        command: RootsCommand,
+    },
+
+    /// Re-index every registered root (and any configured ones)
+    Update {
+        /// Continue interrupted runs from their last checkpoints
+        #[arg(long)]
+        resume: bool,
+        /// Also index the members of zip and tar archives
+        #[arg(long)]
+        archives: bool,
+        /// Store partial content hashes to speed up `dupes`
+        #[arg(long)]
+        hash: bool,
+    },
+
+    /// Drop a file or directory subtree from the index
+    Remove { path: PathBuf },
+
+    /// Re-index a file or directory subtree under an indexed root
+    Reindex {
+        path: PathBuf,
+        /// Also index the members of zip and tar archives
+        #[arg(long)]
+        archives: bool,
+        /// Store partial content hashes to speed up `dupes`
+        #[arg(long)]
+        hash: bool,
+    },
+
+    /// Print the effective configuration as TOML
+    Config,
+
+    /// List groups of indexed files with identical content
+    Dupes {
+        /// Ignore files smaller than this many bytes
+        #[arg(long, default_value_t = 1)]
+        min_size: u64,
+        /// Only compare files below this directory
+        #[arg(long = "in", value_name = "DIR", conflicts_with = "here")]
+        in_dir: Option<PathBuf>,
+        /// Only compare files below the current directory
+        #[arg(long)]
+        here: bool,
+        /// Output format: text, json or ndjson
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
     },
 
     /// Show database statistics
     Stats {
-        #[arg(long, default_value = "results.db")]
-        db: String,
+        /// Output format: text or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
     },
 
     /// Optimize database
-    Vacuum {
-        #[arg(long, default_value = "results.db")]
-        db: String,
-    },
+    Vacuum,
 
     /// Clear database
-    Clear {
-        #[arg(long, default_value = "results.db")]
-        db: String,
+    Clear,
+
+    /// Run a background daemon that owns the database and watches indexed roots
+    #[cfg(all(unix, feature = "daemon"))]
+    Daemon,
+
+    /// Interactive terminal UI with live filtering
+    Tui {
+        /// Initial query
+        query: Vec<String>,
     },
 
     /// Start web GUI server
     Server {
-        #[arg(long, default_value = "127.0.0.1")]
-        host: String,
-        #[arg(long, default_value = "3000")]
-        port: u16,
-        #[arg(long, default_value = "results.db")]
-        db: String,
+        #[arg(long)]
+        host: Option<String>,
+        #[arg(long)]
+        port: Option<u16>,
+        /// File with `<role> <key>` API key lines (roles: read, admin)
+        #[arg(long)]
+        auth_file: Option<PathBuf>,
+        /// Allow browser requests from this origin (repeatable, `*` for any)
+        #[arg(long = "cors-origin")]
+        cors_origins: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RootsCommand {
+    /// Register a directory (or change its settings) and index it
+    Add {
+        path: PathBuf,
+        /// Name used to refer to the root [default: current name, or a unique one from the directory]
+        #[arg(long)]
+        name: Option<String>,
+        /// File or directory name to skip, wildcards allowed (repeatable)
+        #[arg(long = "exclude")]
+        excludes: Vec<String>,
+        /// Content to index: text (known extensions), all, or none
+        #[arg(long, default_value = "text")]
+        content: ContentPolicy,
+        /// Only register the root; index it later with `update`
+        #[arg(long)]
+        no_index: bool,
+    },
+
+    /// Unregister a root by name or path and drop its files
+    Remove { root: String },
+
+    /// List registered roots
+    List {
+        /// Output format: text or json
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
     },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(legacy_args(std::env::args_os().collect()));
+    logging::init(cli.log_format);
+
+    let config = Config::load(cli.config.as_deref())?;
+    let db = resolve_db(cli.db.clone(), &config);
 
     match cli.command {
-        Some(Commands::Index { path, db }) => {
-            index_directory(&path, &db).await?;
+        Some(Commands::Index { path, resume, archives, hash }) => {
+            refuse_daemon_flags(&db, archives, hash)?;
+            let config = Config { archives: config.archives || archives, hash_content: config.hash_content || hash, ..config };
+            let paths = match path {
+                Some(path) => vec![path],
+                None if !config.roots.is_empty() => config.roots.clone(),
+                None => anyhow::bail!(
+                    "no path given and no `roots` configured in {}",
+                    Config::path(cli.config.as_deref())
+                        .map_or_else(|| "the config file".to_string(), |p| p.display().to_string())
+                ),
+            };
+            for path in &paths {
+                index_directory(path, &db, resume, &config).await?;
+            }
         }
 
         Some(Commands::Search {
@@ -91,19 +241,36 @@ async fn main() -> Result<()> {
             max_size,
             limit,
             case_sensitive,
-            db,
+            format,
+            print0,
+            open,
+            exec,
+            first,
+            in_dir,
+            here,
         }) => {
-            let indexer = Indexer::new(&db)?;
+            if print0 && !matches!(format, OutputFormat::Text | OutputFormat::Paths) {
+                anyhow::bail!("--print0 only applies to path output");
+            }
+
             let mut filter = SearchFilter {
-                query,
+                query: join_query(&query),
                 search_content: all,
                 case_sensitive,
-                limit: limit.unwrap_or(100),
+                limit: limit.unwrap_or(config.search.limit),
                 min_size: min_size.unwrap_or(0),
-                max_size: max_size.unwrap_or(u64::MAX),
+                max_size: max_size.or(config.search.max_size).unwrap_or(u64::MAX),
                 ..Default::default()
             };
 
+            if here {
+                filter.path_prefix = Some(std::env::current_dir()?.canonicalize()?);
+            }
+            if let Some(dir) = in_dir {
+                let dir = dir.canonicalize().with_context(|| format!("cannot search in {}", dir.display()))?;
+                filter.path_prefix = Some(dir);
+            }
+
             if let Some(ext_str) = ext {
                 filter.extensions = ext_str
                     .split(',')
@@ -111,7 +278,33 @@ async fn main() -> Result<()> {
                     .collect();
             }
 
-            let results = indexer.search(&filter)?;
+            let mut results = search(&db, &filter)?;
+
+            if first {
+                results.truncate(1);
+                if results.is_empty() {
+                    anyhow::bail!("no results for: {}", filter.query);
+                }
+            }
+            if let Some(n) = open {
+                let result = n
+                    .checked_sub(1)
+                    .and_then(|i| results.get(i))
+                    .ok_or_else(|| anyhow::anyhow!("--open {} is out of range ({} results)", n, results.len()))?;
+                return actions::open_result(result, &filter);
+            }
+            if let Some(command) = exec {
+                return exec_each(&command, &results);
+            }
+            if first {
+                let mut out = std::io::stdout().lock();
+                return ignore_broken_pipe(output::write_results(&mut out, &results, OutputFormat::Paths, print0));
+            }
+
+            if format != OutputFormat::Text || print0 {
+                let mut out = std::io::stdout().lock();
+                return ignore_broken_pipe(output::write_results(&mut out, &results, format, print0));
+            }
 
             if results.is_empty() {
                 println!("❌ No results found for: {}", filter.query);
@@ -124,12 +317,18 @@ async fn main() -> Result<()> {
 
                 for (idx, result) in results.iter().enumerate() {
                     println!("{}. {}", idx + 1, result.path);
-                    println!("   Score: {:.1} | Size: {} KB | ID: {}", 
+                    println!("   Score: {:.1} | Size: {} KB | ID: {}{}", 
                         result.score, 
                         result.size / 1024,
-                        result.file_id
+                        result.file_id,
+                        if result.truncated { " | content truncated" } else { "" }
                     );
 
+                    if !result.metadata.is_empty() {
+                        let fields: Vec<String> =
+                            result.metadata.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                        println!("   Metadata: {}", fields.join(", "));
+                    }
                     if let Some(preview) = &result.matched_content {
                         println!("   Preview: {}", preview);
                     }
@@ -138,88 +337,455 @@ async fn main() -> Result<()> {
             }
         }
 
-        Some(Commands::Stats { db }) => {
-            let indexer = Indexer::new(&db)?;
-            let stats = indexer.get_stats()?;
-            println!("📊 Database Statistics:\n{}", 
-                serde_json::to_string_pretty(&stats)?);
+        Some(Commands::Roots { command: RootsCommand::Add { path, name, excludes, content, no_index } }) => {
+            let mut root = Root::new(path.canonicalize()?);
+            // Left empty, the name comes from the directory and is made unique.
+            root.name = name.unwrap_or_default();
+            root.excludes = excludes;
+            root.content = content;
+            let root = add_root(&db, root)?;
+            println!("📁 Registered root `{}` at {}", root.name, root.path.display());
+            if !no_index {
+                index_directory(&root.path, &db, false, &config).await?;
+            }
+        }
+
+        Some(Commands::Roots { command: RootsCommand::Remove { root } }) => {
+            let files = remove_root(&db, &root)?;
+            println!("🗑️  Removed root `{}` and {} entries", root, files);
+        }
+
+        Some(Commands::Roots { command: RootsCommand::List { format } }) => {
+            let roots = list_roots(&db)?;
+            match format {
+                OutputFormat::Text if roots.is_empty() => {
+                    println!("No roots registered; add one with `rust-search roots add <PATH>`")
+                }
+                OutputFormat::Text => {
+                    for root in &roots {
+                        println!("{}  {}", root.name, root.path.display());
+                        println!(
+                            "   Content: {:?} | Excludes: {} | Last indexed: {}",
+                            root.content,
+                            if root.excludes.is_empty() { "-".to_string() } else { root.excludes.join(", ") },
+                            root.last_indexed_at.as_deref().unwrap_or("never")
+                        );
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&roots)?),
+                OutputFormat::Ndjson => {
+                    for root in &roots {
+                        println!("{}", serde_json::to_string(root)?);
+                    }
+                }
+                other => anyhow::bail!("roots list does not support {:?} output; use text or json", other),
+            }
+        }
+
+        Some(Commands::Update { resume, archives, hash }) => {
+            refuse_daemon_flags(&db, archives, hash)?;
+            let config = Config { archives: config.archives || archives, hash_content: config.hash_content || hash, ..config };
+            let mut roots = list_roots(&db)?;
+            for path in &config.roots {
+                let path = path.canonicalize()?;
+                if !roots.iter().any(|root| root.path == path) {
+                    roots.push(add_root(&db, Root::new(path))?);
+                }
+            }
+            if roots.is_empty() {
+                anyhow::bail!("no roots registered; add one with `rust-search roots add <PATH>`");
+            }
+            for root in &roots {
+                println!("🔄 Updating `{}` ({})", root.name, root.path.display());
+                index_directory(&root.path, &db, resume, &config).await?;
+            }
+        }
+
+        Some(Commands::Remove { path }) => {
+            let files = remove_path(&db, &path)?;
+            println!("🗑️  Removed {} entries under {}", files, path.display());
+        }
+
+        Some(Commands::Reindex { path, archives, hash }) => {
+            refuse_daemon_flags(&db, archives, hash)?;
+            let config = Config { archives: config.archives || archives, hash_content: config.hash_content || hash, ..config };
+            let files = reindex_path(&db, &path, &config).await?;
+            println!("✅ Re-indexed {} files under {}", files, path.display());
         }
 
-        Some(Commands::Vacuum { db }) => {
+        Some(Commands::Config) => {
+            print!("{}", toml::to_string_pretty(&Config { db: Some(PathBuf::from(&db)), ..config })?);
+        }
+
+        Some(Commands::Dupes { min_size, in_dir, here, format }) => {
+            let path_prefix = match in_dir {
+                Some(dir) => Some(dir.canonicalize().with_context(|| format!("cannot search in {}", dir.display()))?),
+                None if here => Some(std::env::current_dir()?.canonicalize()?),
+                None => None,
+            };
+            let report = duplicates(&db, &DuplicateFilter { path_prefix, min_size })?;
+            match format {
+                OutputFormat::Text => {
+                    if report.groups.is_empty() {
+                        println!("✅ No duplicate files found");
+                    } else {
+                        println!(
+                            "🗂️  {} groups, {} redundant copies, {} KB wasted\n",
+                            report.groups.len(),
+                            report.duplicate_files,
+                            report.wasted / 1024
+                        );
+                    }
+                    for (idx, group) in report.groups.iter().enumerate() {
+                        println!(
+                            "{}. {} copies of {} KB ({} KB wasted) | MD5: {}",
+                            idx + 1,
+                            group.files.len(),
+                            group.size / 1024,
+                            group.wasted / 1024,
+                            group.hash
+                        );
+                        for file in &group.files {
+                            println!("   {}", file.path);
+                        }
+                        println!();
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                OutputFormat::Ndjson => {
+                    for group in &report.groups {
+                        println!("{}", serde_json::to_string(group)?);
+                    }
+                }
+                other => anyhow::bail!("dupes does not support {:?} output; use text, json or ndjson", other),
+            }
+        }
+
+        Some(Commands::Stats { format }) => {
+            let stats = stats(&db)?;
+            match format {
+                OutputFormat::Text => println!("📊 Database Statistics:\n{}", 
+                    serde_json::to_string_pretty(&stats)?),
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+                OutputFormat::Ndjson => println!("{}", serde_json::to_string(&stats)?),
+                other => anyhow::bail!("stats does not support {:?} output; use text or json", other),
+            }
+        }
+
+        Some(Commands::Vacuum) => {
             let indexer = Indexer::new(&db)?;
             indexer.vacuum()?;
         }
 
-        Some(Commands::Clear { db }) => {
+        Some(Commands::Clear) => {
             let indexer = Indexer::new(&db)?;
             indexer.clear()?;
         }
 
-        Some(Commands::Server { host, port, db }) => {
-            println!("🚀 Starting server on http://{}:{}", host, port);
-            start_server(&host, port, &db).await?;
+        #[cfg(all(unix, feature = "daemon"))]
+        Some(Commands::Daemon) => {
+            rust_search::daemon::run(&db, config.index_settings()).await?;
         }
 
-        None => {
-            // Legacy support: auto-detect usage pattern
-            if cli.args.is_empty() {
-                print_help();
-                return Ok(());
-            }
+        Some(Commands::Tui { query }) => {
+            run_tui(&db, &query.join(" "))?;
+        }
 
-            // Check for -n flag (search with limit)
-            if cli.args.len() >= 2 && cli.args[0] == "-n" {
-                let limit: usize = cli.args[1].parse().unwrap_or(100);
-                let query = cli.args[2..].join(" ");
-                
-                if query.is_empty() {
-                    eprintln!("❌ Error: -n requires limit and query");
-                    return Ok(());
-                }
+        Some(Commands::Server { host, port, auth_file, cors_origins }) => {
+            let host = host.unwrap_or_else(|| config.server.host.clone());
+            let port = port.unwrap_or(config.server.port);
+            let auth_file = auth_file.or_else(|| config.server.auth_file.clone());
+            let cors_origins = if cors_origins.is_empty() {
+                config.server.cors_origins.clone()
+            } else {
+                cors_origins
+            };
+            info!(host = %host, port, "starting server");
+            start_server(&host, port, &db, auth_file, cors_origins, &config).await?;
+        }
 
-                let indexer = Indexer::new("results.db")?;
-                let filter = SearchFilter {
-                    query,
-                    limit,
-                    ..Default::default()
-                };
-                let results = indexer.search(&filter)?;
-                print_results(&results);
-                return Ok(());
+        None => print_help(),
+    }
+
+    Ok(())
+}
+
+/// Global options that take a value, which may precede a legacy query.
+const GLOBAL_VALUE_OPTIONS: &[&str] = &["--db", "--config", "--log-format"];
+
+/// Rewrites the v0.1 command lines into subcommands so they share the
+/// subcommands' parsing and options:
+///
+/// - `rust-search <QUERY>...` becomes `search <QUERY>...`
+/// - `rust-search -n <N> <QUERY>` becomes `search -n <N> <QUERY>`
+/// - `rust-search <DIR>` becomes `index <DIR>`
+/// - `rust-search -i [QUERY]` becomes `tui [QUERY]`
+///
+/// Search flags such as `--all` or `--first` may lead a legacy query. When
+/// the first word starts with `-`, the query words are moved after a `--`
+/// so one like `-foo` is searched for; elsewhere such words need an
+/// explicit `--`.
+fn legacy_args(args: Vec<OsString>) -> Vec<OsString> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i).map(|a| a.to_string_lossy()) {
+        if GLOBAL_VALUE_OPTIONS.contains(&arg.as_ref()) {
+            i += 2;
+        } else if GLOBAL_VALUE_OPTIONS.iter().any(|o| arg.starts_with(&format!("{}=", o))) {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+
+    let Some(first) = args.get(i).map(|a| a.to_string_lossy().to_string()) else {
+        return args;
+    };
+    let is_builtin = Cli::command()
+        .get_subcommands()
+        .any(|c| c.get_name() == first || c.get_all_aliases().any(|a| a == first));
+    if is_builtin || matches!(first.as_str(), "help" | "-h" | "--help" | "-V" | "--version") {
+        return args;
+    }
+
+    let (head, rest) = args.split_at(i);
+    let mut rewritten = head.to_vec();
+    match first.as_str() {
+        "-i" | "--interactive" => {
+            rewritten.push("tui".into());
+            rewritten.extend_from_slice(&rest[1..]);
+        }
+        _ if rest.len() == 1 && Path::new(&rest[0]).is_dir() => {
+            rewritten.push("index".into());
+            rewritten.extend_from_slice(rest);
+        }
+        _ => {
+            rewritten.push("search".into());
+            // A query such as `-foo` would otherwise be rejected as an
+            // unknown option.
+            if rest.iter().any(|arg| arg == "--") || !first.starts_with('-') {
+                rewritten.extend_from_slice(rest);
+            } else {
+                rewritten.extend(query_after_options(rest));
             }
+        }
+    }
+    rewritten
+}
 
-            // Auto-index if directory
-            if let Some(first_arg) = cli.args.first() {
-                if std::path::Path::new(first_arg).is_dir() {
-                    let path = PathBuf::from(first_arg);
-                    index_directory(&path, "results.db").await?;
-                    return Ok(());
+/// Puts the options in `args` before a `--` and the query words after it,
+/// so query words starting with `-` are not taken for unknown options.
+fn query_after_options(args: &[OsString]) -> Vec<OsString> {
+    let mut command = Cli::command();
+    command.build();
+    let Some(search) = command.find_subcommand("search") else {
+        return args.to_vec();
+    };
+    let (mut options, mut words) = (Vec::new(), Vec::new());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let word = arg.to_string_lossy();
+        if word == "--" {
+            words.extend(args.by_ref().cloned());
+            break;
+        }
+        let option = match word.strip_prefix("--") {
+            Some(long) => search
+                .get_arguments()
+                .find(|a| a.get_long() == long.split('=').next())
+                .map(|a| (a, !long.contains('='))),
+            None if word.starts_with('-') => search
+                .get_arguments()
+                .find(|a| a.get_short().is_some() && a.get_short() == word.chars().nth(1))
+                .map(|a| (a, word.chars().count() == 2)),
+            None => None,
+        };
+        match option {
+            Some((option, separate_value)) => {
+                options.push(arg.clone());
+                if separate_value && option.get_action().takes_values() {
+                    options.extend(args.next().cloned());
                 }
             }
+            None => words.push(arg.clone()),
+        }
+    }
+    options.push("--".into());
+    options.extend(words);
+    options
+}
+
+/// Joins query arguments, re-quoting `field:value` terms whose value the
+/// shell unquoted, so `artist:"Nina Simone"` stays one term.
+fn join_query(words: &[String]) -> String {
+    let extractors = rust_search::extract::builtin();
+    let fields = rust_search::extract::fields(&extractors);
+    let is_field_term = |word: &str| {
+        word.parse::<MetadataFilter>()
+            .is_ok_and(|filter| fields.iter().any(|f| f.eq_ignore_ascii_case(&filter.field)))
+    };
+    words
+        .iter()
+        .map(|word| match word.split_once(':') {
+            Some((field, value)) if word.contains(char::is_whitespace) && is_field_term(word) => {
+                format!("{}:\"{}\"", field, value)
+            }
+            _ => word.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-            // Otherwise search with default settings
-            let query = cli.args.join(" ");
-            let indexer = Indexer::new("results.db")?;
-            let filter = SearchFilter {
-                query,
-                ..Default::default()
-            };
-            let results = indexer.search(&filter)?;
-            print_results(&results);
+/// Treats a closed stdout (e.g. piping into `head`) as a normal exit.
+fn ignore_broken_pipe(result: std::io::Result<()>) -> Result<()> {
+    match result {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => Ok(other?),
+    }
+}
+
+/// Picks the database: `--db`, then the config and `RUST_SEARCH_DB`, then
+/// the XDG data directory.
+fn resolve_db(flag: Option<String>, config: &Config) -> String {
+    if let Some(db) = flag {
+        return db;
+    }
+    let db = config.db_path();
+    if config.db.is_none() && Path::new("results.db").exists() && db != Path::new("results.db") {
+        warn!(
+            "ignoring ./results.db from an older version; using {} (pass --db results.db to keep using it)",
+            db.display()
+        );
+    }
+    db.to_string_lossy().to_string()
+}
+
+/// Runs `command` for every result, continuing past failures.
+fn exec_each(command: &str, results: &[SearchResult]) -> Result<()> {
+    let mut failed = 0;
+    for result in results {
+        if !actions::exec_template(command, &result.os_path())?.success() {
+            failed += 1;
         }
     }
+    if failed > 0 {
+        anyhow::bail!("`{}` failed for {} of {} results", command, failed, results.len());
+    }
+    Ok(())
+}
+
+/// Searches through the daemon when one serves `db`, otherwise opens it directly.
+fn search(db: &str, filter: &SearchFilter) -> Result<Vec<SearchResult>> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.search(filter);
+    }
+    Indexer::new(db)?.search(filter)
+}
+
+fn stats(db: &str) -> Result<DatabaseStats> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.stats();
+    }
+    Indexer::new(db)?.get_stats()
+}
 
+fn duplicates(db: &str, filter: &DuplicateFilter) -> Result<DuplicateReport> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.duplicates(filter);
+    }
+    Indexer::new(db)?.duplicates(filter)
+}
+
+fn add_root(db: &str, root: Root) -> Result<Root> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.add_root(root);
+    }
+    Indexer::new(db)?.register_root(root)
+}
+
+fn remove_root(db: &str, root: &str) -> Result<u64> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.remove_root(root);
+    }
+    Indexer::new(db)?.remove_root(root)
+}
+
+fn list_roots(db: &str) -> Result<Vec<Root>> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.roots();
+    }
+    Indexer::new(db)?.root_list()
+}
+
+fn remove_path(db: &str, path: &Path) -> Result<u64> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.remove(path);
+    }
+    Indexer::new(db)?.remove_path(path)
+}
+
+/// Fails when `--archives` or `--hash` is passed while a daemon serves the
+/// database: it indexes with the settings it was started with, so the flags
+/// would be ignored.
+fn refuse_daemon_flags(db: &str, archives: bool, hash: bool) -> Result<()> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if (archives || hash) && rust_search::daemon::Client::connect(db).is_some() {
+        let flags = match (archives, hash) {
+            (true, true) => "--archives and --hash",
+            (true, false) => "--archives",
+            _ => "--hash",
+        };
+        anyhow::bail!(
+            "a running daemon serves {} and indexes with its own settings, so {} would be ignored; \
+             set `archives`/`hash_content` (or RUST_SEARCH_ARCHIVES/RUST_SEARCH_HASH) for the daemon \
+             and restart it, or stop it first",
+            db,
+            flags
+        );
+    }
+    #[cfg(not(all(unix, feature = "daemon")))]
+    let _ = (db, archives, hash);
     Ok(())
 }
 
-async fn index_directory(path: &std::path::Path, db: &str) -> Result<()> {
+async fn reindex_path(db: &str, path: &Path, config: &Config) -> Result<u64> {
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        return client.reindex(path);
+    }
+    Indexer::with_settings(db, config.index_settings())?.reindex_path(path, None).await
+}
+
+async fn index_directory(path: &Path, db: &str, resume: bool, config: &Config) -> Result<()> {
     use walkdir::WalkDir;
 
-    println!("📚 Indexing directory: {}", path.display());
-    
+    #[cfg(all(unix, feature = "daemon"))]
+    if let Some(mut client) = rust_search::daemon::Client::connect(db) {
+        info!(path = %path.display(), "indexing through running daemon");
+        let files = client.index(path, resume)?;
+        println!("✅ Indexed {} files", files);
+        return Ok(());
+    }
+
+    let path = &path.canonicalize()?;
+    let indexer = Indexer::with_settings(db, config.index_settings())?;
+    indexer.add_root(path)?;
+    let checkpoint = if resume { indexer.checkpoint(path)? } else { None };
+    if resume && checkpoint.is_none() {
+        println!("ℹ️  No checkpoint for {}, indexing from the start", path.display());
+    }
+
     // Count files first
+    let roots = indexer.root_list()?;
     let file_count = WalkDir::new(path)
         .into_iter()
+        .filter_entry(|e| !indexer.is_excluded_in(e.path(), &roots))
         .filter_map(|e| e.ok())
         .filter(|e| e.path().is_file())
         .count();
@@ -232,25 +798,32 @@ async fn index_directory(path: &std::path::Path, db: &str) -> Result<()> {
             .unwrap()
             .progress_chars("=>-")
     );
+    let already_indexed = checkpoint.as_ref().map_or(0, |c| c.files_indexed);
+    pb.set_position(already_indexed);
 
-    let indexer = Indexer::new(db)?;
-    let mut count = 0u64;
+    let stop = shutdown::stop_flag();
+    let progress = |count: u64| pb.set_position(already_indexed + count);
+    let outcome = indexer
+        .index_dir_with(path, &IndexOptions {
+            resume,
+            stop: Some(&stop),
+            progress: Some(&progress),
+        })
+        .await?;
 
-    for entry in WalkDir::new(path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file())
-    {
-        let file_path = entry.path();
-        if let Ok(_file_info) = indexer.create_file_info_public(file_path).await {
-            count += 1;
-            pb.inc(1);
-        }
+    if outcome.completed {
+        pb.finish_with_message(format!("✅ Indexed {} files", already_indexed + outcome.files));
+    } else {
+        pb.abandon();
+        println!(
+            "\n⏸  Interrupted after {} files, progress saved. Continue with:\n   rust-search index --resume {} --db {}",
+            already_indexed + outcome.files,
+            path.display(),
+            db
+        );
     }
-
-    pb.finish_with_message(format!("✅ Indexed {} files", count));
     println!();
-    
+
     Ok(())
 }
 
@@ -259,49 +832,226 @@ fn print_help() {
     println!("USAGE:");
     println!("    rust-search [COMMAND] [OPTIONS]\n");
     println!("COMMANDS:");
-    println!("    index <PATH>              Index a directory");
+    println!("    index [PATH]              Index a directory, or the configured roots");
+    println!("                              (--resume continues after Ctrl-C, --archives reads");
+    println!("                              zip/tar members as bundle.zip!/path, --hash stores");
+    println!("                              partial content hashes for dupes)");
     println!("    search <QUERY>            Search indexed files");
+    println!("    dupes                     List files with identical content");
+    println!("                              (--min-size <BYTES>, --in <DIR>, --here, --format)");
     println!("    stats                     Show database statistics");
+    println!("    roots add <PATH>          Register a root (--name, --exclude, --content text|all|none)");
+    println!("    roots remove <NAME>       Unregister a root and drop its files");
+    println!("    roots list                List roots with their settings and last index time");
+    println!("    update                    Re-index every registered root");
+    println!("    remove <PATH>             Drop a file or subtree from the index");
+    println!("    reindex <PATH>            Refresh a file or subtree without a full rebuild");
+    println!("    config                    Print the effective configuration");
     println!("    vacuum                    Optimize database");
     println!("    clear                     Clear database");
-    println!("    server                    Start web GUI server\n");
-    println!("LEGACY SUPPORT (v0.1):");
-    println!("    rust-search <QUERY>       Search by filename");
-    println!("    rust-search -n <N> <Q>    Search with limit N");
-    println!("    rust-search <PATH>        Auto-index directory\n");
+    println!("    daemon                    Own the database, watch roots, serve CLI queries");
+    println!("    tui [QUERY]               Interactive search (also: rust-search -i [QUERY])");
+    println!("    server                    Start web GUI server");
+    println!("                              (--auth-file, RUST_SEARCH_API_KEYS, RUST_SEARCH_ADMIN_KEYS,");
+    println!("                               --cors-origin)\n");
+    println!("GLOBAL OPTIONS:");
+    println!("    --db <PATH>               Database (default: ~/.local/share/rust-search/index.db)");
+    println!("    --config <FILE>           Config file (default: ~/.config/rust-search/config.toml)");
+    println!("    --log-format <FMT>        Log format: text or json (levels via RUST_LOG)\n");
+    println!("ENVIRONMENT:");
+    println!("    RUST_SEARCH_CONFIG, RUST_SEARCH_DB, RUST_SEARCH_ROOTS, RUST_SEARCH_EXCLUDES,");
+    println!("    RUST_SEARCH_TEXT_EXTENSIONS, RUST_SEARCH_BINARY_EXTENSIONS, RUST_SEARCH_ENCODING,");
+    println!("    RUST_SEARCH_MAX_CONTENT_SIZE, RUST_SEARCH_ARCHIVES, RUST_SEARCH_HASH, RUST_SEARCH_LIMIT,");
+    println!("    RUST_SEARCH_HOST, RUST_SEARCH_PORT\n");
+    println!("SHORTHANDS (v0.1 forms, accept every search/index option):");
+    println!("    rust-search <QUERY>       Same as: search <QUERY>");
+    println!("    rust-search -n <N> <Q>    Same as: search -n <N> <Q>");
+    println!("    rust-search --first <Q>   Same as: search --first <Q>");
+    println!("    rust-search -i [QUERY]    Same as: tui [QUERY]");
+    println!("    rust-search <PATH>        Same as: index <PATH>\n");
     println!("SEARCH OPTIONS:");
     println!("    --all                     Search file contents (not just names)");
     println!("    --ext <EXT>               Filter by extension (e.g., --ext rs,py)");
     println!("    --min-size <SIZE>         Minimum file size in bytes");
     println!("    --max-size <SIZE>         Maximum file size in bytes");
-    println!("    --limit <N>               Maximum results (default: 100)");
-    println!("    --case-sensitive          Case-sensitive search\n");
+    println!("    -n, --limit <N>           Maximum results (default: 100, see [search] limit)");
+    println!("    --case-sensitive          Case-sensitive search");
+    println!("    --format <FMT>            text, json, ndjson, csv, tsv or paths");
+    println!("    -0, --print0              NUL-separated paths (for xargs -0)");
+    println!("    --open <N>                Open result N ($EDITOR +line for content hits)");
+    println!("    --exec <CMD>              Run CMD for each result, {{}} is the path");
+    println!("    --first                   Print only the best path");
+    println!("    --in <DIR>                Only results below DIR");
+    println!("    --here                    Only results below the current directory");
+    println!("    FIELD:VALUE               Filter on image/audio metadata, e.g. width:>3000,");
+    println!("                              artist:\"Nina Simone\", taken:>=2021 (also :=, :<, :<=)");
+    println!("                              Fields: width, height, camera, taken, gps, artist,");
+    println!("                              album, title, year, genre; other words are searched\n");
     println!("EXAMPLES:");
     println!("    rust-search index /home   # Index home directory");
     println!("    rust-search main          # Search for 'main' (legacy)");
     println!("    rust-search -n 10 test    # Search 'test' limit 10 (legacy)");
+    println!("    rust-search --all -n 5 fn main  # Legacy form with search options");
     println!("    rust-search search test   # Search for 'test' (new)");
     println!("    rust-search search --all --ext rs # Full-text search in Rust");
+    println!("    rust-search search -- -v    # Queries starting with - go after --");
+    println!("    rust-search search -0 --ext log err | xargs -0 ls -l  # Pipe paths");
+    println!("    vim $(rust-search --first main.rs)  # Edit the best match");
+    println!("    rust-search --here --all TODO  # Content search in this project only");
+    println!("    rust-search search --all TODO --open 1  # Jump to the first TODO");
+    println!("    rust-search search --ext jpg camera:canon width:>=4000  # Large Canon photos");
+    println!("    rust-search dupes --min-size 1048576  # Duplicates of 1 MiB and up");
     println!("    rust-search server --port 3000  # Start GUI on port 3000");
 }
 
-fn print_results(results: &[rust_search::SearchResult]) {
-    if results.is_empty() {
-        println!("❌ No results found");
-    } else {
-        println!("✅ Found {} results:\n", results.len());
-        for (idx, result) in results.iter().enumerate() {
-            println!("{}. {}", idx + 1, result.path);
-            if let Some(preview) = &result.matched_content {
-                println!("   {}", preview);
-            }
-        }
-    }
+#[cfg(feature = "tui")]
+fn run_tui(db: &str, query: &str) -> Result<()> {
+    rust_search::tui::run(db, query)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_db: &str, _query: &str) -> Result<()> {
+    anyhow::bail!("this build does not include the terminal UI (enable the `tui` feature)")
 }
 
-async fn start_server(host: &str, port: u16, db: &str) -> Result<()> {
-    println!("Soon");
-    println!("📝 Note: Web server implementation coming soon!");
+#[cfg(feature = "server")]
+async fn start_server(
+    host: &str,
+    port: u16,
+    db: &str,
+    auth_file: Option<PathBuf>,
+    cors_origins: Vec<String>,
+    config: &Config,
+) -> Result<()> {
+    rust_search::web_server::start_server(rust_search::web_server::ServerConfig {
+        host: host.to_string(),
+        port,
+        db: db.to_string(),
+        auth_file,
+        cors_origins,
+        index: config.index_settings(),
+    })
+    .await
+}
+
+#[cfg(not(feature = "server"))]
+async fn start_server(
+    _host: &str,
+    _port: u16,
+    _db: &str,
+    _auth_file: Option<PathBuf>,
+    _cors_origins: Vec<String>,
+    _config: &Config,
+) -> Result<()> {
+    println!("📝 Note: this build was compiled without the `server` feature");
     println!("   For now, use CLI: rust-search search <query>");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Commands {
+        let args = std::iter::once("rust-search").chain(args.iter().copied()).map(OsString::from);
+        Cli::try_parse_from(legacy_args(args.collect()))
+            .unwrap()
+            .command
+            .unwrap()
+    }
+
+    #[test]
+    fn test_legacy_query() {
+        match parse(&["main", "loop"]) {
+            Commands::Search { query, limit, all, .. } => {
+                assert_eq!(query, vec!["main", "loop"]);
+                assert_eq!(limit, None);
+                assert!(!all);
+            }
+            _ => panic!("expected search"),
+        }
+    }
+
+    #[test]
+    fn test_legacy_limit_with_options() {
+        match parse(&["--db", "x.db", "-n", "10", "test", "--all", "--ext", "rs"]) {
+            Commands::Search { query, limit, all, ext, .. } => {
+                assert_eq!(query, vec!["test"]);
+                assert_eq!(limit, Some(10));
+                assert!(all);
+                assert_eq!(ext.as_deref(), Some("rs"));
+            }
+            _ => panic!("expected search"),
+        }
+    }
+
+    #[test]
+    fn test_hyphen_queries() {
+        for args in [&["-foo"][..], &["-n", "5", "-foo"], &["search", "--", "-foo"], &["-n", "5", "--", "-foo"]] {
+            match parse(args) {
+                Commands::Search { query, .. } => assert_eq!(query, vec!["-foo"]),
+                _ => panic!("expected search"),
+            }
+        }
+        match parse(&["--all", "--", "-x", "-y"]) {
+            Commands::Search { query, all, .. } => {
+                assert_eq!(query, vec!["-x", "-y"]);
+                assert!(all);
+            }
+            _ => panic!("expected search"),
+        }
+        match parse(&["-foo", "bar", "-n", "3", "--format=json"]) {
+            Commands::Search { query, limit, format, .. } => {
+                assert_eq!(query, vec!["-foo", "bar"]);
+                assert_eq!(limit, Some(3));
+                assert_eq!(format, OutputFormat::Json);
+            }
+            _ => panic!("expected search"),
+        }
+        match parse(&["main", "--all", "--format", "json"]) {
+            Commands::Search { query, all, format, .. } => {
+                assert_eq!(query, vec!["main"]);
+                assert!(all);
+                assert_eq!(format, OutputFormat::Json);
+            }
+            _ => panic!("expected search"),
+        }
+    }
+
+    #[test]
+    fn test_join_query() {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(join_query(&words(&["artist:Nina Simone", "jazz"])), "artist:\"Nina Simone\" jazz");
+        assert_eq!(join_query(&words(&["std::io Read"])), "std::io Read");
+        assert_eq!(join_query(&words(&["key:a b"])), "key:a b");
+    }
+
+    #[test]
+    fn test_legacy_first_and_interactive() {
+        assert!(matches!(parse(&["--first", "main.rs"]), Commands::Search { first: true, .. }));
+        match parse(&["-i", "main"]) {
+            Commands::Tui { query } => assert_eq!(query, vec!["main"]),
+            _ => panic!("expected tui"),
+        }
+    }
+
+    #[test]
+    fn test_legacy_directory_indexes() {
+        let dir = std::env::temp_dir();
+        match parse(&[dir.to_str().unwrap()]) {
+            Commands::Index { path, resume, .. } => {
+                assert_eq!(path, Some(dir));
+                assert!(!resume);
+            }
+            _ => panic!("expected index"),
+        }
+    }
+
+    #[test]
+    fn test_subcommands_untouched() {
+        assert!(matches!(parse(&["stats"]), Commands::Stats { .. }));
+        assert!(matches!(parse(&["--db=x.db", "search", "q"]), Commands::Search { .. }));
+        let args: Vec<OsString> = ["rust-search", "--help"].iter().map(OsString::from).collect();
+        assert_eq!(legacy_args(args.clone()), args);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::extract::{Extracted, Extractor};
+use anyhow::{bail, Result};
+use exif::{In, Tag, Value};
+use id3::TagLike;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+/// Dimensions of JPEG, TIFF, PNG, GIF and WebP images, plus the camera,
+/// capture date and GPS position from EXIF data when present.
+///
+/// Fields: `width`, `height`, `camera`, `taken` (`YYYY-MM-DD HH:MM:SS`) and
+/// `gps` (`latitude,longitude` in decimal degrees).
+pub struct ImageMetadata;
+
+impl Extractor for ImageMetadata {
+    fn name(&self) -> &str {
+        "image"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["jpg", "jpeg", "tif", "tiff", "png", "gif", "webp"]
+    }
+
+    fn fields(&self) -> &[&str] {
+        &["width", "height", "camera", "taken", "gps"]
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<Extracted> {
+        let mut metadata = BTreeMap::new();
+        if let Ok(size) = imagesize::blob_size(bytes) {
+            metadata.insert("width".to_string(), size.width.to_string());
+            metadata.insert("height".to_string(), size.height.to_string());
+        }
+        if let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(bytes)) {
+            let text = |tag| exif.get_field(tag, In::PRIMARY).and_then(|field| ascii(&field.value));
+            let camera = match (text(Tag::Make), text(Tag::Model)) {
+                (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+                (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+                (make, model) => make.or(model),
+            };
+            if let Some(camera) = camera {
+                metadata.insert("camera".to_string(), camera);
+            }
+            if let Some(taken) = text(Tag::DateTimeOriginal).or_else(|| text(Tag::DateTime)) {
+                // EXIF writes dates as `2021:06:01 12:00:00`.
+                metadata.insert("taken".to_string(), taken.replacen(':', "-", 2));
+            }
+            let coordinate = |tag, reference, negative| {
+                let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+                    Value::Rational(parts) if parts.len() == 3 => {
+                        parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0
+                    }
+                    _ => return None,
+                };
+                Some(if text(reference).as_deref() == Some(negative) { -degrees } else { degrees })
+            };
+            if let (Some(lat), Some(lon)) = (
+                coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+                coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+            ) {
+                metadata.insert("gps".to_string(), format!("{:.6},{:.6}", lat, lon));
+            }
+        }
+        if metadata.is_empty() {
+            bail!("not a recognised image");
+        }
+        Ok(Extracted { text: String::new(), metadata })
+    }
+}
+
+/// Tags of MP3 (ID3), FLAC and Ogg Vorbis or Opus (Vorbis comments) files.
+///
+/// Fields: `artist`, `album`, `title`, `year` and `genre`.
+pub struct AudioTags;
+
+impl Extractor for AudioTags {
+    fn name(&self) -> &str {
+        "audio"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mp3", "flac", "ogg", "oga", "opus"]
+    }
+
+    fn fields(&self) -> &[&str] {
+        &["artist", "album", "title", "year", "genre"]
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<Extracted> {
+        let mut metadata = BTreeMap::new();
+        if let Some(comments) = flac_comments(bytes).or_else(|| ogg_comments(bytes)) {
+            for (key, value) in comments {
+                let field = match key.as_str() {
+                    "artist" | "album" | "title" | "genre" => key,
+                    "date" | "year" => "year".to_string(),
+                    _ => continue,
+                };
+                let value = if field == "year" { value.chars().take(4).collect() } else { value };
+                metadata.entry(field).or_insert(value);
+            }
+        } else {
+            let tag = id3::Tag::read_from2(Cursor::new(bytes))?;
+            let fields = [
+                ("artist", tag.artist().map(str::to_string)),
+                ("album", tag.album().map(str::to_string)),
+                ("title", tag.title().map(str::to_string)),
+                ("year", tag.year().or_else(|| tag.date_recorded().map(|date| date.year)).map(|y| y.to_string())),
+                ("genre", tag.genre_parsed().map(|genre| genre.into_owned())),
+            ];
+            for (field, value) in fields {
+                if let Some(value) = value {
+                    metadata.insert(field.to_string(), value);
+                }
+            }
+        }
+        metadata.retain(|_, value| !value.trim().is_empty());
+        Ok(Extracted { text: String::new(), metadata })
+    }
+}
+
+/// The first string of an EXIF ASCII value, without trailing NULs or spaces.
+fn ascii(value: &Value) -> Option<String> {
+    match value {
+        Value::Ascii(strings) => {
+            let text = String::from_utf8_lossy(strings.first()?);
+            let text = text.trim_end_matches(['\0', ' ']).trim();
+            (!text.is_empty()).then(|| text.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The Vorbis comment block of a FLAC file.
+fn flac_comments(bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut pos = bytes.strip_prefix(b"fLaC").map(|_| 4)?;
+    loop {
+        let header = bytes.get(pos..pos + 4)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let block = bytes.get(pos + 4..pos + 4 + len)?;
+        if header[0] & 0x7f == 4 {
+            return vorbis_comments(block);
+        }
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        pos += 4 + len;
+    }
+}
+
+/// The comment header of an Ogg Vorbis or Opus stream. The header is looked
+/// up by its signature, which is enough for tags that fit in one page.
+fn ogg_comments(bytes: &[u8]) -> Option<Vec<(String, String)>> {
+    if !bytes.starts_with(b"OggS") {
+        return None;
+    }
+    [&b"\x03vorbis"[..], b"OpusTags"].iter().find_map(|signature| {
+        let start = bytes.windows(signature.len()).position(|window| window == *signature)?;
+        vorbis_comments(&bytes[start + signature.len()..])
+    })
+}
+
+/// Parses a Vorbis comment list: a vendor string, then `KEY=value` entries,
+/// each prefixed by its little-endian length. Keys are lowercased.
+fn vorbis_comments(data: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut pos = 0;
+    let vendor = read_u32_le(data, &mut pos)?;
+    take(data, &mut pos, vendor)?;
+    let count = read_u32_le(data, &mut pos)?;
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let Some(entry) = read_u32_le(data, &mut pos).and_then(|len| take(data, &mut pos, len)) else {
+            break;
+        };
+        let entry = String::from_utf8_lossy(entry);
+        if let Some((key, value)) = entry.split_once('=') {
+            comments.push((key.to_lowercase(), value.to_string()));
+        }
+    }
+    Some(comments)
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let slice = data.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    Some(slice)
+}
+
+fn read_u32_le(data: &[u8], pos: &mut usize) -> Option<usize> {
+    take(data, pos, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 2, 0, 0, 0, 0, 0, 0, 0]);
+        bytes
+    }
+
+    /// A little-endian TIFF IFD holding `entries` of (tag, type, count,
+    /// value), with values over four bytes stored after it.
+    fn ifd(start: u32, entries: &[(u16, u16, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut data: Vec<u8> = Vec::new();
+        let data_start = start + 2 + 12 * entries.len() as u32 + 4;
+        out.extend((entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            out.extend(tag.to_le_bytes());
+            out.extend(kind.to_le_bytes());
+            out.extend(count.to_le_bytes());
+            if value.len() <= 4 {
+                let mut inline = value.clone();
+                inline.resize(4, 0);
+                out.extend(inline);
+            } else {
+                out.extend((data_start + data.len() as u32).to_le_bytes());
+                data.extend(value);
+            }
+        }
+        out.extend(0u32.to_le_bytes());
+        out.extend(data);
+        out
+    }
+
+    fn ascii_entry(tag: u16, text: &str) -> (u16, u16, u32, Vec<u8>) {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        (tag, 2, value.len() as u32, value)
+    }
+
+    fn rationals(tag: u16, parts: &[(u32, u32)]) -> (u16, u16, u32, Vec<u8>) {
+        let value = parts.iter().flat_map(|(n, d)| n.to_le_bytes().into_iter().chain(d.to_le_bytes())).collect();
+        (tag, 5, parts.len() as u32, value)
+    }
+
+    fn jpeg_with_exif() -> Vec<u8> {
+        let exif_ifd = |start| ifd(start, &[ascii_entry(0x9003, "2021:06:01 12:30:00")]);
+        let gps_ifd = |start| {
+            ifd(start, &[
+                ascii_entry(0x1, "N"),
+                rationals(0x2, &[(48, 1), (51, 1), (30, 1)]),
+                ascii_entry(0x3, "W"),
+                rationals(0x4, &[(2, 1), (17, 1), (24, 1)]),
+            ])
+        };
+        let ifd0 = |exif: u32, gps: u32| {
+            ifd(8, &[
+                ascii_entry(0x10f, "Canon"),
+                ascii_entry(0x110, "EOS 5D"),
+                (0x8769, 4, 1, exif.to_le_bytes().to_vec()),
+                (0x8825, 4, 1, gps.to_le_bytes().to_vec()),
+            ])
+        };
+        let exif_start = 8 + ifd0(0, 0).len() as u32;
+        let gps_start = exif_start + exif_ifd(0).len() as u32;
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend(ifd0(exif_start, gps_start));
+        tiff.extend(exif_ifd(exif_start));
+        tiff.extend(gps_ifd(gps_start));
+
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        jpeg.extend((tiff.len() as u16 + 8).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff);
+        // Baseline frame header: 8-bit, 3000 high, 4000 wide, one component.
+        jpeg.extend(b"\xff\xc0\0\x0b\x08\x0b\xb8\x0f\xa0\x01\x01\x11\x00");
+        jpeg.extend(b"\xff\xd9");
+        jpeg
+    }
+
+    fn vorbis_block(comments: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend(4u32.to_le_bytes());
+        block.extend(b"test");
+        block.extend((comments.len() as u32).to_le_bytes());
+        for comment in comments {
+            block.extend((comment.len() as u32).to_le_bytes());
+            block.extend(comment.as_bytes());
+        }
+        block
+    }
+
+    #[test]
+    fn test_image_metadata() {
+        let png = ImageMetadata.extract(&png(4000, 3000)).unwrap();
+        assert_eq!(png.metadata["width"], "4000");
+        assert_eq!(png.metadata["height"], "3000");
+        assert_eq!(png.text, "");
+
+        let gif = ImageMetadata.extract(b"GIF89a\x40\x01\xf0\x00\x00\x00\x00;").unwrap();
+        assert_eq!((gif.metadata["width"].as_str(), gif.metadata["height"].as_str()), ("320", "240"));
+
+        let jpeg = ImageMetadata.extract(&jpeg_with_exif()).unwrap();
+        assert_eq!(jpeg.metadata["width"], "4000");
+        assert_eq!(jpeg.metadata["height"], "3000");
+        assert_eq!(jpeg.metadata["camera"], "Canon EOS 5D");
+        assert_eq!(jpeg.metadata["taken"], "2021-06-01 12:30:00");
+        assert_eq!(jpeg.metadata["gps"], "48.858333,-2.290000");
+
+        assert!(ImageMetadata.extract(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_audio_tags() {
+        let block = vorbis_block(&["ARTIST=Nina Simone", "TITLE=Sinnerman", "DATE=1965-04-01", "TRACKNUMBER=10"]);
+        let mut flac = b"fLaC\0\0\0\x22".to_vec();
+        flac.extend([0; 34]);
+        flac.push(0x84);
+        flac.extend(&(block.len() as u32).to_be_bytes()[1..]);
+        flac.extend(block);
+        let tags = AudioTags.extract(&flac).unwrap().metadata;
+        assert_eq!(tags["artist"], "Nina Simone");
+        assert_eq!(tags["title"], "Sinnerman");
+        assert_eq!(tags["year"], "1965");
+        assert!(!tags.contains_key("tracknumber"));
+
+        let mut tag = id3::Tag::new();
+        tag.set_artist("Miles Davis");
+        tag.set_album("Kind of Blue");
+        tag.set_year(1959);
+        let mut mp3 = Vec::new();
+        tag.write_to(&mut mp3, id3::Version::Id3v24).unwrap();
+        mp3.extend([0xff, 0xfb, 0x90, 0x00]);
+        let tags = AudioTags.extract(&mp3).unwrap().metadata;
+        assert_eq!(tags["artist"], "Miles Davis");
+        assert_eq!(tags["album"], "Kind of Blue");
+        assert_eq!(tags["year"], "1959");
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the query latency histogram buckets.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Upper bounds of the per-query result count histogram buckets.
+const RESULT_BUCKETS: &[f64] = &[0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+/// An `f64` that can be updated from several threads.
+#[derive(Debug, Default)]
+pub struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, value: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+}
+
+/// A Prometheus-style cumulative histogram with fixed buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicF64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicF64::default(),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        if let Some(idx) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.add(value);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum.get());
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// Counters updated by the indexer while it walks directories.
+#[derive(Debug, Default)]
+pub struct IndexingMetrics {
+    pub files_indexed: AtomicU64,
+    pub runs: AtomicU64,
+    pub last_run_files: AtomicU64,
+    pub last_run_seconds: AtomicF64,
+}
+
+impl IndexingMetrics {
+    pub fn record_run(&self, files: u64, elapsed: Duration) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.last_run_files.store(files, Ordering::Relaxed);
+        self.last_run_seconds.set(elapsed.as_secs_f64());
+    }
+}
+
+/// Query-side metrics collected by the web server.
+#[derive(Debug)]
+pub struct QueryMetrics {
+    pub queries: AtomicU64,
+    pub errors: AtomicU64,
+    pub latency: Histogram,
+    pub results: Histogram,
+}
+
+impl Default for QueryMetrics {
+    fn default() -> Self {
+        Self {
+            queries: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            latency: Histogram::new(LATENCY_BUCKETS),
+            results: Histogram::new(RESULT_BUCKETS),
+        }
+    }
+}
+
+impl QueryMetrics {
+    pub fn record(&self, elapsed: Duration, results: Option<usize>) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.latency.observe(elapsed.as_secs_f64());
+        match results {
+            Some(count) => self.results.observe(count as f64),
+            None => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Point-in-time values read from the database for a scrape.
+#[derive(Debug, Default)]
+pub struct IndexGauges {
+    pub documents: usize,
+    pub disk_bytes: u64,
+    /// Unix time of the last completed indexing run, if any.
+    pub last_indexed: Option<i64>,
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub fn render(queries: &QueryMetrics, indexing: &IndexingMetrics, gauges: &IndexGauges) -> String {
+    let mut out = String::new();
+
+    counter(
+        &mut out,
+        "rust_search_queries_total",
+        "Search queries served",
+        queries.queries.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "rust_search_query_errors_total",
+        "Search queries that failed",
+        queries.errors.load(Ordering::Relaxed),
+    );
+    queries.latency.render(
+        &mut out,
+        "rust_search_query_duration_seconds",
+        "Search query latency",
+    );
+    queries.results.render(
+        &mut out,
+        "rust_search_query_results",
+        "Results returned per query",
+    );
+
+    gauge(
+        &mut out,
+        "rust_search_documents",
+        "Files in the index",
+        gauges.documents as f64,
+    );
+    gauge(
+        &mut out,
+        "rust_search_db_disk_bytes",
+        "Disk space used by the sled database",
+        gauges.disk_bytes as f64,
+    );
+    if let Some(ts) = gauges.last_indexed {
+        gauge(
+            &mut out,
+            "rust_search_last_indexed_timestamp_seconds",
+            "Unix time of the last completed indexing run",
+            ts as f64,
+        );
+    }
+
+    counter(
+        &mut out,
+        "rust_search_indexed_files_total",
+        "Files indexed by this process",
+        indexing.files_indexed.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "rust_search_index_runs_total",
+        "Indexing runs completed by this process",
+        indexing.runs.load(Ordering::Relaxed),
+    );
+    let last_files = indexing.last_run_files.load(Ordering::Relaxed);
+    let last_seconds = indexing.last_run_seconds.get();
+    gauge(
+        &mut out,
+        "rust_search_last_index_duration_seconds",
+        "Duration of the last indexing run",
+        last_seconds,
+    );
+    let throughput = if last_seconds > 0.0 {
+        last_files as f64 / last_seconds
+    } else {
+        0.0
+    };
+    gauge(
+        &mut out,
+        "rust_search_last_index_files_per_second",
+        "Throughput of the last indexing run",
+        throughput,
+    );
+
+    out
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_is_cumulative() {
+        let histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        histogram.observe(10.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "h", "test");
+        assert!(out.contains("h_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("h_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("h_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("h_sum 13.5\n"));
+        assert!(out.contains("h_count 3\n"));
+    }
+
+    #[test]
+    fn test_render_counts_errors() {
+        let queries = QueryMetrics::default();
+        queries.record(Duration::from_millis(3), Some(7));
+        queries.record(Duration::from_millis(3), None);
+
+        let out = render(
+            &queries,
+            &IndexingMetrics::default(),
+            &IndexGauges::default(),
+        );
+        assert!(out.contains("rust_search_queries_total 2\n"));
+        assert!(out.contains("rust_search_query_errors_total 1\n"));
+        assert!(!out.contains("rust_search_last_indexed_timestamp_seconds"));
+    }
+}
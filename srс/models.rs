@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
@@ -10,20 +12,151 @@ pub struct SearchResult {
     pub matched_content: Option<String>,
     pub created_at: String,
     pub modified_at: String,
+    /// Exact OS bytes of the path when it is not valid UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_bytes: Option<Vec<u8>>,
+    /// Whether only the start of the file's content was indexed.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Fields found by a content extractor.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl SearchResult {
+    /// The file's real path, which `path` only approximates for names
+    /// that are not valid UTF-8.
+    pub fn os_path(&self) -> PathBuf {
+        path_from_parts(&self.path, self.path_bytes.as_deref())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
     pub id: String,
+    /// Display form of the path; see [`FileInfo::os_path`] for the real one.
     pub path: String,
     pub size: u64,
     pub content: Option<String>,
     pub extension: String,
     pub created_at: String,
     pub modified_at: String,
+    /// Exact OS bytes of the path when it is not valid UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_bytes: Option<Vec<u8>>,
+    /// Whether `content` stops at the size limit before the end of the file.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Fields found by a content extractor, such as a title or author.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// MD5 of the file's first and last bytes, taken when content hashing
+    /// is enabled; see [`IndexSettings::hash_content`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_hash: Option<String>,
+}
+
+impl FileInfo {
+    pub fn os_path(&self) -> PathBuf {
+        path_from_parts(&self.path, self.path_bytes.as_deref())
+    }
+}
+
+/// The raw bytes of `path` when it is not valid UTF-8 and so cannot be
+/// held exactly by a `String`.
+pub fn raw_path_bytes(path: &Path) -> Option<Vec<u8>> {
+    if path.to_str().is_some() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(path.as_os_str().as_bytes().to_vec())
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+/// Rebuilds a path from its display string and optional raw bytes.
+pub fn path_from_parts(display: &str, raw: Option<&[u8]>) -> PathBuf {
+    match raw {
+        Some(raw) => path_from_bytes(raw),
+        None => PathBuf::from(display),
+    }
+}
+
+/// The bytes identifying `path` in keys and ids: the exact OS bytes on
+/// Unix, so distinct names never collide.
+pub fn path_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// The inverse of [`path_bytes`].
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
 }
 
-#[derive(Debug, Clone, Default)]
+/// Serde representation for stored paths: a string when the path is valid
+/// UTF-8, its raw bytes otherwise. Both forms are accepted when reading.
+pub(crate) mod os_path {
+    use super::{path_bytes, path_from_bytes};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(text) => serializer.serialize_str(text),
+            None => serializer.serialize_bytes(&path_bytes(path)),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => PathBuf::from(text),
+            Repr::Bytes(bytes) => path_from_bytes(&bytes),
+        })
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::path::PathBuf;
+
+        pub fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+            match path {
+                Some(path) => super::serialize(path, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+            Ok(Option::<super::Repr>::deserialize(deserializer)?.map(|repr| match repr {
+                super::Repr::Text(text) => PathBuf::from(text),
+                super::Repr::Bytes(bytes) => super::path_from_bytes(&bytes),
+            }))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchFilter {
     pub query: String,
     pub search_content: bool,
@@ -32,6 +165,212 @@ pub struct SearchFilter {
     pub min_size: u64,
     pub max_size: u64,
     pub limit: usize,
+    /// Match file names by fuzzy subsequence instead of substring.
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Only return files below this directory.
+    #[serde(default, with = "os_path::option")]
+    pub path_prefix: Option<PathBuf>,
+    /// Conditions on extracted metadata, on top of any `field:value` terms
+    /// in `query`.
+    #[serde(default)]
+    pub metadata: Vec<MetadataFilter>,
+}
+
+/// How a [`MetadataFilter`] compares a field with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    /// The field contains the value, ignoring case; numbers must be equal.
+    Matches,
+    /// The field equals the value, ignoring case.
+    Equals,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+/// A condition on one metadata field, written in queries as `field:value`,
+/// `field:=value`, `field:>value`, `field:>=value`, `field:<value` or
+/// `field:<=value`.
+///
+/// Ordering compares numbers numerically and anything else as text, so
+/// `width:>3000` and `taken:>=2021-06` both work.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataFilter {
+    pub field: String,
+    pub op: Comparison,
+    pub value: String,
+}
+
+impl MetadataFilter {
+    /// Whether `metadata` has the field and it satisfies the condition.
+    pub fn matches(&self, metadata: &BTreeMap<String, String>) -> bool {
+        let Some(actual) = metadata.get(&self.field.to_lowercase()) else {
+            return false;
+        };
+        let numbers = actual.trim().parse::<f64>().ok().zip(self.value.trim().parse::<f64>().ok());
+        let ordering = match numbers {
+            Some((actual, wanted)) => actual.partial_cmp(&wanted),
+            None => Some(actual.to_lowercase().cmp(&self.value.to_lowercase())),
+        };
+        match self.op {
+            Comparison::Matches if numbers.is_none() => {
+                actual.to_lowercase().contains(&self.value.to_lowercase())
+            }
+            Comparison::Matches | Comparison::Equals => ordering == Some(std::cmp::Ordering::Equal),
+            Comparison::Greater => ordering == Some(std::cmp::Ordering::Greater),
+            Comparison::GreaterOrEqual => ordering.is_some_and(|o| o.is_ge()),
+            Comparison::Less => ordering == Some(std::cmp::Ordering::Less),
+            Comparison::LessOrEqual => ordering.is_some_and(|o| o.is_le()),
+        }
+    }
+}
+
+impl FromStr for MetadataFilter {
+    type Err = String;
+
+    /// Parses a single `field:value` term, without quotes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, rest) = s
+            .split_once(':')
+            .ok_or_else(|| format!("`{}` is not a field:value term", s))?;
+        let valid_field = field.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && field.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+        // `http://...`, `C:\...` and `std::io` are paths, not field terms.
+        if !valid_field || rest.starts_with("//") || rest.starts_with('\\') || rest.starts_with(':') {
+            return Err(format!("`{}` is not a field:value term", s));
+        }
+        let (op, value) = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equals),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| rest.strip_prefix(prefix).map(|value| (op, value)))
+        .unwrap_or((Comparison::Matches, rest));
+        if value.is_empty() {
+            return Err(format!("`{}` has no value", s));
+        }
+        Ok(Self { field: field.to_lowercase(), op, value: value.to_string() })
+    }
+}
+
+/// Splits a query into its plain search text and its `field:value` terms on
+/// one of the known metadata `fields`.
+///
+/// Terms are separated by whitespace; double quotes group words, as in
+/// `artist:"Nina Simone"`. Anything that is not a field term is kept, in
+/// order, as the search text, so `std::io::Read` or `key:value` on an
+/// unknown field are searched for as written.
+pub fn split_metadata_terms(query: &str, fields: &[&str]) -> (String, Vec<MetadataFilter>) {
+    let mut words = Vec::new();
+    let mut filters = Vec::new();
+    let mut chars = query.chars().peekable();
+    while chars.peek().is_some() {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut raw = String::new();
+        let mut unquoted = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next_if(|c| quoted || !c.is_whitespace()) {
+            raw.push(c);
+            if c == '"' {
+                quoted = !quoted;
+            } else {
+                unquoted.push(c);
+            }
+        }
+        if raw.is_empty() {
+            continue;
+        }
+        let known = |filter: &MetadataFilter| fields.iter().any(|f| f.eq_ignore_ascii_case(&filter.field));
+        match unquoted.parse::<MetadataFilter>() {
+            Ok(filter) if !raw.starts_with('"') && known(&filter) => filters.push(filter),
+            _ => words.push(raw),
+        }
+    }
+    if filters.is_empty() {
+        return (query.to_string(), filters);
+    }
+    (words.join(" "), filters)
+}
+
+/// What the indexer walks into and which files it reads as text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexSettings {
+    /// File or directory names to skip; `*` and `?` wildcards are allowed.
+    pub excludes: Vec<String>,
+    /// Extensions read as text without sniffing; empty means the built-in list.
+    pub text_extensions: Vec<String>,
+    /// Extensions never read for content; empty means the built-in list.
+    #[serde(default)]
+    pub binary_extensions: Vec<String>,
+    /// Encoding for 8-bit text that is not UTF-8, such as `latin1` or
+    /// `windows-1251`; guessed per file when unset.
+    #[serde(default)]
+    pub fallback_encoding: Option<String>,
+    /// Bytes of content kept per file; the default limit when unset.
+    #[serde(default)]
+    pub max_content_size: Option<u64>,
+    /// Whether members of zip and tar archives are indexed as virtual paths.
+    #[serde(default)]
+    pub archives: bool,
+    /// Whether a partial content hash is stored with each file, which makes
+    /// duplicate detection cheaper.
+    #[serde(default)]
+    pub hash_content: bool,
+}
+
+/// Which files [`Indexer::duplicates`](crate::Indexer::duplicates) compares.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateFilter {
+    /// Only compare files below this directory.
+    #[serde(default, with = "os_path::option")]
+    pub path_prefix: Option<PathBuf>,
+    /// Ignore files smaller than this many bytes.
+    #[serde(default)]
+    pub min_size: u64,
+}
+
+/// One copy in a [`DuplicateGroup`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateFile {
+    pub file_id: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_bytes: Option<Vec<u8>>,
+    pub modified_at: String,
+}
+
+impl DuplicateFile {
+    pub fn os_path(&self) -> PathBuf {
+        path_from_parts(&self.path, self.path_bytes.as_deref())
+    }
+}
+
+/// Files with identical content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    /// MD5 of the whole content.
+    pub hash: String,
+    /// Size of each copy in bytes.
+    pub size: u64,
+    /// Bytes that removing all but one copy would free.
+    pub wasted: u64,
+    pub files: Vec<DuplicateFile>,
+}
+
+/// Every group of duplicates found, largest waste first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    /// Copies beyond the first in each group.
+    pub duplicate_files: usize,
+    /// Total of `wasted` over all groups.
+    pub wasted: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,4 +379,133 @@ pub struct DatabaseStats {
     pub total_size: String,
     pub indexed_at: String,
     pub extensions: HashMap<String, usize>,
+    /// On-disk layout version of the database.
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// Progress of an interrupted indexing run over one root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Last entry visited; everything before it in walk order is indexed.
+    #[serde(with = "os_path")]
+    pub last_path: PathBuf,
+    pub files_indexed: u64,
+    pub updated_at: String,
+}
+
+/// Which files of a root have their content indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentPolicy {
+    /// Files with a known text extension.
+    #[default]
+    Text,
+    /// Every file that reads as text.
+    All,
+    /// No content; only names and metadata are searchable.
+    NamesOnly,
+}
+
+impl FromStr for ContentPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ContentPolicy::Text),
+            "all" => Ok(ContentPolicy::All),
+            "none" | "names-only" | "names_only" => Ok(ContentPolicy::NamesOnly),
+            other => Err(format!("unknown content policy `{}` (expected text, all or none)", other)),
+        }
+    }
+}
+
+/// A directory registered for indexing, with its own settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Root {
+    /// Short unique name used to refer to the root from the CLI.
+    pub name: String,
+    #[serde(with = "os_path")]
+    pub path: PathBuf,
+    /// Names skipped under this root, on top of the global excludes.
+    #[serde(default)]
+    pub excludes: Vec<String>,
+    #[serde(default)]
+    pub content: ContentPolicy,
+    /// When the last complete indexing run finished, as RFC 3339.
+    #[serde(default)]
+    pub last_indexed_at: Option<String>,
+}
+
+impl Root {
+    /// A root at `path` with default settings, named after its directory.
+    pub fn new(path: PathBuf) -> Self {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "root".to_string());
+        Self {
+            name,
+            path,
+            excludes: Vec::new(),
+            content: ContentPolicy::default(),
+            last_indexed_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_metadata_terms() {
+        let split = |query| split_metadata_terms(query, &["width", "artist", "taken", "year"]);
+        let (text, filters) = split("holiday width:>3000 artist:\"Nina Simone\" jpg");
+        assert_eq!(text, "holiday jpg");
+        assert_eq!(
+            filters,
+            vec![
+                MetadataFilter { field: "width".to_string(), op: Comparison::Greater, value: "3000".to_string() },
+                MetadataFilter { field: "artist".to_string(), op: Comparison::Matches, value: "Nina Simone".to_string() },
+            ]
+        );
+
+        assert_eq!(split("main  fn").0, "main  fn");
+        assert_eq!(split("https://example.com").1, vec![]);
+        assert_eq!(split("C:\\Users").1, vec![]);
+        assert_eq!(split("\"note:1\" year:").1, vec![]);
+        assert_eq!(split("taken:>=2021-06").1[0].op, Comparison::GreaterOrEqual);
+
+        // Code and unknown fields stay search text.
+        assert_eq!(split("std::io::Read"), ("std::io::Read".to_string(), vec![]));
+        assert_eq!(split("use std::io fn"), ("use std::io fn".to_string(), vec![]));
+        assert_eq!(split("key:value width::3"), ("key:value width::3".to_string(), vec![]));
+        assert_eq!(split("Width:10 key:value").0, "key:value");
+        assert!("std::io".parse::<MetadataFilter>().is_err());
+    }
+
+    #[test]
+    fn test_metadata_filter_matches() {
+        let metadata: BTreeMap<String, String> = [
+            ("width", "4000"),
+            ("camera", "Canon EOS 5D"),
+            ("taken", "2021-06-01 12:30:00"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let matches = |term: &str| term.parse::<MetadataFilter>().unwrap().matches(&metadata);
+
+        assert!(matches("width:>3000"));
+        assert!(matches("width:4000.0"));
+        assert!(!matches("width:400"));
+        assert!(!matches("width:<4000"));
+        assert!(matches("camera:canon"));
+        assert!(!matches("camera:=canon"));
+        assert!(matches("camera:=canon eos 5d"));
+        assert!(matches("taken:>=2021-06"));
+        assert!(!matches("taken:<2021"));
+        assert!(!matches("artist:canon"));
+    }
 }
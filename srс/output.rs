@@ -0,0 +1,187 @@
+use crate::SearchResult;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// How search results are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human-readable listing with scores and previews.
+    #[default]
+    Text,
+    /// A single JSON array.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    Csv,
+    Tsv,
+    /// Bare paths, one per line.
+    Paths,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "paths" => Ok(OutputFormat::Paths),
+            other => Err(format!(
+                "unknown format `{}` (expected text, json, ndjson, csv, tsv or paths)",
+                other
+            )),
+        }
+    }
+}
+
+const COLUMNS: &[&str] = &["path", "score", "size", "file_id", "modified_at", "matched_content", "truncated"];
+
+/// Writes `results` in one of the machine-readable formats.
+///
+/// `OutputFormat::Text` is rendered by the CLI itself and is treated like
+/// `Paths` here. With `print0` paths are NUL-terminated instead of
+/// newline-terminated, for `xargs -0`.
+pub fn write_results<W: Write>(
+    out: &mut W,
+    results: &[SearchResult],
+    format: OutputFormat,
+    print0: bool,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, results)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Ndjson => {
+            for result in results {
+                serde_json::to_writer(&mut *out, result)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => write_delimited(out, results, ',', csv_field)?,
+        OutputFormat::Tsv => write_delimited(out, results, '\t', tsv_field)?,
+        OutputFormat::Text | OutputFormat::Paths => {
+            let terminator = if print0 { b"\0" as &[u8] } else { b"\n" };
+            for result in results {
+                out.write_all(&crate::models::path_bytes(&result.os_path()))?;
+                out.write_all(terminator)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn write_delimited<W: Write>(
+    out: &mut W,
+    results: &[SearchResult],
+    delimiter: char,
+    escape: fn(&str) -> String,
+) -> io::Result<()> {
+    let separator = delimiter.to_string();
+    writeln!(out, "{}", COLUMNS.join(&separator))?;
+    for result in results {
+        let row = [
+            escape(&result.path),
+            format!("{:.1}", result.score),
+            result.size.to_string(),
+            escape(&result.file_id),
+            escape(&result.modified_at),
+            escape(result.matched_content.as_deref().unwrap_or("")),
+            result.truncated.to_string(),
+        ];
+        writeln!(out, "{}", row.join(&separator))?;
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks are escaped instead.
+fn tsv_field(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, preview: Option<&str>) -> SearchResult {
+        SearchResult {
+            file_id: "id".to_string(),
+            path: path.to_string(),
+            score: 75.0,
+            size: 10,
+            matched_content: preview.map(str::to_string),
+            created_at: "2024-01-19".to_string(),
+            modified_at: "2024-01-19".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: Default::default(),
+        }
+    }
+
+    fn render(results: &[SearchResult], format: OutputFormat, print0: bool) -> String {
+        let mut out = Vec::new();
+        write_results(&mut out, results, format, print0).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_paths_and_print0() {
+        let results = [result("/a b.rs", None), result("/c.rs", None)];
+        assert_eq!(render(&results, OutputFormat::Paths, false), "/a b.rs\n/c.rs\n");
+        assert_eq!(render(&results, OutputFormat::Paths, true), "/a b.rs\0/c.rs\0");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_paths_keep_raw_bytes() {
+        let mut raw = result("/bad\u{fffd}name.rs", None);
+        raw.path_bytes = Some(b"/bad\xffname.rs".to_vec());
+        let mut out = Vec::new();
+        write_results(&mut out, &[raw], OutputFormat::Paths, true).unwrap();
+        assert_eq!(out, b"/bad\xffname.rs\0");
+    }
+
+    #[test]
+    fn test_csv_and_tsv_escaping() {
+        let results = [result("/x,y.rs", Some("say \"hi\"\tnow"))];
+        let csv = render(&results, OutputFormat::Csv, false);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "\"/x,y.rs\",75.0,10,id,2024-01-19,\"say \"\"hi\"\"\tnow\",false"
+        );
+
+        let tsv = render(&results, OutputFormat::Tsv, false);
+        assert_eq!(
+            tsv.lines().nth(1).unwrap(),
+            "/x,y.rs\t75.0\t10\tid\t2024-01-19\tsay \"hi\"\\tnow\tfalse"
+        );
+    }
+
+    #[test]
+    fn test_ndjson_one_object_per_line() {
+        let results = [result("/a.rs", None), result("/b.rs", None)];
+        let ndjson = render(&results, OutputFormat::Ndjson, false);
+        let parsed: Vec<SearchResult> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].path, "/b.rs");
+    }
+}
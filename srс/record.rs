@@ -0,0 +1,322 @@
+use crate::models::FileInfo;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+/// Leading byte of records in the current binary layout. Records written
+/// as JSON by earlier versions start with `{` and are still readable.
+pub const RECORD_VERSION: u8 = 1;
+
+/// Content shorter than this is stored as is; compressing it saves little.
+const COMPRESS_MIN: usize = 256;
+
+/// zstd level for stored content, favouring indexing speed.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Most content a record may hold. The indexer keeps stored content below
+/// it, so a larger length can only come from a corrupt record and is
+/// refused before anything is allocated for it.
+pub const MAX_CONTENT_LEN: u64 = 256 * 1024 * 1024;
+
+const TRUNCATED: u8 = 1;
+const CONTENT: u8 = 1 << 1;
+const COMPRESSED: u8 = 1 << 2;
+const PATH_BYTES: u8 = 1 << 3;
+const PARTIAL_HASH: u8 = 1 << 4;
+
+const TIME_NANOS: u8 = 0;
+const TIME_TEXT: u8 = 1;
+
+/// Encodes a record for the files tree.
+///
+/// After the version byte come a flags byte, the size and the fields in
+/// declaration order; strings and byte strings are prefixed by their
+/// length as a LEB128 varint and absent optional fields are left out.
+/// Timestamps written by the indexer take 8 bytes instead of their RFC 3339
+/// text. Content goes last, zstd-compressed once it is long enough, so
+/// [`decode_without_content`] can stop before it.
+pub fn encode(file: &FileInfo) -> Vec<u8> {
+    let packed = file
+        .content
+        .as_deref()
+        .filter(|content| content.len() >= COMPRESS_MIN)
+        .and_then(|content| {
+            let packed = zstd::bulk::compress(content.as_bytes(), ZSTD_LEVEL).ok()?;
+            (packed.len() < content.len()).then_some(packed)
+        });
+
+    let mut flags = 0;
+    if file.truncated {
+        flags |= TRUNCATED;
+    }
+    if file.content.is_some() {
+        flags |= CONTENT;
+    }
+    if packed.is_some() {
+        flags |= COMPRESSED;
+    }
+    if file.path_bytes.is_some() {
+        flags |= PATH_BYTES;
+    }
+    if file.partial_hash.is_some() {
+        flags |= PARTIAL_HASH;
+    }
+
+    let mut out = Vec::with_capacity(128 + file.content.as_ref().map_or(0, |c| c.len() / 3));
+    out.push(RECORD_VERSION);
+    out.push(flags);
+    write_varint(&mut out, file.size);
+    write_bytes(&mut out, file.id.as_bytes());
+    write_bytes(&mut out, file.path.as_bytes());
+    if let Some(raw) = &file.path_bytes {
+        write_bytes(&mut out, raw);
+    }
+    write_bytes(&mut out, file.extension.as_bytes());
+    write_time(&mut out, &file.created_at);
+    write_time(&mut out, &file.modified_at);
+    if let Some(hash) = &file.partial_hash {
+        write_bytes(&mut out, hash.as_bytes());
+    }
+    write_varint(&mut out, file.metadata.len() as u64);
+    for (key, value) in &file.metadata {
+        write_bytes(&mut out, key.as_bytes());
+        write_bytes(&mut out, value.as_bytes());
+    }
+    match (file.content.as_deref(), packed) {
+        (Some(raw), Some(packed)) => {
+            write_varint(&mut out, raw.len() as u64);
+            write_bytes(&mut out, &packed);
+        }
+        (Some(raw), None) => write_bytes(&mut out, raw.as_bytes()),
+        (None, _) => {}
+    }
+    out
+}
+
+/// Decodes a stored record in any supported layout.
+pub fn decode(bytes: &[u8]) -> Result<FileInfo> {
+    decode_with(bytes, true)
+}
+
+/// Decodes a stored record without its content, skipping the cost of
+/// decompressing it.
+pub fn decode_without_content(bytes: &[u8]) -> Result<FileInfo> {
+    decode_with(bytes, false)
+}
+
+fn decode_with(bytes: &[u8], with_content: bool) -> Result<FileInfo> {
+    match bytes.first() {
+        Some(b'{') => {
+            let mut file: FileInfo = serde_json::from_slice(bytes)?;
+            if !with_content {
+                file.content = None;
+            }
+            Ok(file)
+        }
+        Some(&RECORD_VERSION) => decode_v1(&mut Reader { bytes: &bytes[1..] }, with_content)
+            .context("corrupt file record"),
+        Some(version) => bail!("unsupported file record version {}", version),
+        None => bail!("empty file record"),
+    }
+}
+
+fn decode_v1(input: &mut Reader, with_content: bool) -> Result<FileInfo> {
+    let flags = input.byte()?;
+    let size = input.varint()?;
+    let id = input.string()?;
+    let path = input.string()?;
+    let path_bytes = match flags & PATH_BYTES {
+        0 => None,
+        _ => Some(input.bytes()?.to_vec()),
+    };
+    let extension = input.string()?;
+    let created_at = input.time()?;
+    let modified_at = input.time()?;
+    let partial_hash = match flags & PARTIAL_HASH {
+        0 => None,
+        _ => Some(input.string()?),
+    };
+    let mut metadata = BTreeMap::new();
+    for _ in 0..input.varint()? {
+        let key = input.string()?;
+        metadata.insert(key, input.string()?);
+    }
+    let content = match (flags & CONTENT != 0 && with_content, flags & COMPRESSED != 0) {
+        (false, _) => None,
+        (true, false) => Some(input.string()?),
+        (true, true) => {
+            let len = input.varint()?;
+            if len > MAX_CONTENT_LEN {
+                bail!("content length {} exceeds {} bytes", len, MAX_CONTENT_LEN);
+            }
+            let raw = zstd::bulk::decompress(input.bytes()?, len as usize)?;
+            Some(String::from_utf8(raw)?)
+        }
+    };
+    Ok(FileInfo {
+        id,
+        path,
+        size,
+        content,
+        extension,
+        created_at,
+        modified_at,
+        path_bytes,
+        truncated: flags & TRUNCATED != 0,
+        metadata,
+        partial_hash,
+    })
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Stores an RFC 3339 timestamp as nanoseconds when formatting them back
+/// gives the same text, and as text otherwise.
+fn write_time(out: &mut Vec<u8>, time: &str) {
+    let nanos = DateTime::parse_from_rfc3339(time)
+        .ok()
+        .and_then(|parsed| parsed.timestamp_nanos_opt())
+        .filter(|&nanos| Utc.timestamp_nanos(nanos).to_rfc3339() == time);
+    match nanos {
+        Some(nanos) => {
+            out.push(TIME_NANOS);
+            out.extend_from_slice(&nanos.to_le_bytes());
+        }
+        None => {
+            out.push(TIME_TEXT);
+            write_bytes(out, time.as_bytes());
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            bail!("record ends early");
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("varint too long")
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = usize::try_from(self.varint()?)?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        Ok(std::str::from_utf8(self.bytes()?)?.to_string())
+    }
+
+    fn time(&mut self) -> Result<String> {
+        match self.byte()? {
+            TIME_NANOS => {
+                let nanos = i64::from_le_bytes(self.take(8)?.try_into()?);
+                Ok(Utc.timestamp_nanos(nanos).to_rfc3339())
+            }
+            TIME_TEXT => self.string(),
+            other => bail!("unknown timestamp tag {}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(content: Option<&str>) -> FileInfo {
+        FileInfo {
+            id: "5d41402abc4b2a76b9719d911017c592".to_string(),
+            path: "/home/user/notes.txt".to_string(),
+            size: 1234,
+            content: content.map(str::to_string),
+            extension: "txt".to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            modified_at: "2024-01-19".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: BTreeMap::new(),
+            partial_hash: None,
+        }
+    }
+
+    fn assert_same(a: &FileInfo, b: &FileInfo) {
+        assert_eq!(serde_json::to_value(a).unwrap(), serde_json::to_value(b).unwrap());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let plain = sample(Some("short"));
+        assert_same(&decode(&encode(&plain)).unwrap(), &plain);
+
+        let mut full = sample(Some(&"fn main() { println!(\"hi\"); }\n".repeat(500)));
+        full.path_bytes = Some(b"/home/user/caf\xe9".to_vec());
+        full.truncated = true;
+        full.partial_hash = Some("0123456789abcdef0123456789abcdef".to_string());
+        full.metadata.insert("width".to_string(), "4000".to_string());
+        full.metadata.insert("camera".to_string(), "Canon EOS 5D".to_string());
+        let encoded = encode(&full);
+        assert_eq!(encoded[0], RECORD_VERSION);
+        assert_ne!(encoded[1] & COMPRESSED, 0);
+        assert!(encoded.len() * 10 < serde_json::to_vec(&full).unwrap().len());
+        assert_same(&decode(&encoded).unwrap(), &full);
+
+        let headers = decode_without_content(&encoded).unwrap();
+        assert_eq!(headers.content, None);
+        assert_eq!(headers.metadata, full.metadata);
+
+        let empty = sample(None);
+        assert_same(&decode(&encode(&empty)).unwrap(), &empty);
+    }
+
+    #[test]
+    fn test_legacy_and_corrupt_records() {
+        let file = sample(Some("from json"));
+        let json = serde_json::to_vec(&file).unwrap();
+        assert_same(&decode(&json).unwrap(), &file);
+        assert_eq!(decode_without_content(&json).unwrap().content, None);
+
+        let encoded = encode(&file);
+        assert!(decode(&encoded[..encoded.len() - 3]).is_err());
+        assert!(decode(&[9, 0]).is_err());
+        assert!(decode(&[]).is_err());
+
+        // A compressed length far beyond what was stored must not be allocated.
+        let mut inflated = encode(&sample(None));
+        inflated[1] |= CONTENT | COMPRESSED;
+        write_varint(&mut inflated, u64::MAX >> 1);
+        write_bytes(&mut inflated, &zstd::bulk::compress(b"tiny", ZSTD_LEVEL).unwrap());
+        assert!(decode(&inflated).is_err());
+    }
+}
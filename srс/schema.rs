@@ -0,0 +1,242 @@
+use crate::{models, record};
+use anyhow::{bail, Context, Result};
+use sled::Db;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Version of the on-disk layout written by this build.
+///
+/// 1. Path index and named root records.
+/// 2. File records in the binary layout of [`record`].
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Tree holding database-wide settings such as the schema version.
+pub const META_TREE: &str = "meta";
+
+const VERSION_KEY: &[u8] = b"schema_version";
+
+/// One step of the upgrade path, from the version before `to`.
+struct Migration {
+    to: u32,
+    description: &'static str,
+    run: fn(&Db) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { to: 1, description: "build the path index and name roots", run: paths_and_roots },
+    Migration { to: 2, description: "re-encode file records as binary", run: binary_records },
+];
+
+/// Opens the database at `path` and upgrades it to [`SCHEMA_VERSION`].
+///
+/// New databases are stamped with the current version. Older ones are first
+/// copied to a backup next to them, see [`backup_path`], then migrated step
+/// by step; the stored version only advances once a step has finished, so
+/// an interrupted upgrade resumes on the next open. Databases written by a
+/// newer build are refused rather than misread.
+pub fn open(path: &str) -> Result<Db> {
+    let db = sled::open(path)?;
+    let version = match stored_version(&db)? {
+        Some(version) => version,
+        None if is_empty(&db)? => {
+            set_version(&db, SCHEMA_VERSION)?;
+            return Ok(db);
+        }
+        // Databases from before versioning have no marker.
+        None => 0,
+    };
+    if version > SCHEMA_VERSION {
+        bail!(
+            "database {} uses schema version {}, but this rust-search only supports up to version {}; \
+             upgrade rust-search to open it",
+            path,
+            version,
+            SCHEMA_VERSION
+        );
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(db);
+    }
+
+    // Copy the files only once sled has released them.
+    db.flush()?;
+    drop(db);
+    let backup = backup_path(Path::new(path), version);
+    if backup.exists() {
+        info!(backup = %backup.display(), "keeping backup from an earlier upgrade attempt");
+    } else {
+        copy_dir(Path::new(path), &backup)
+            .with_context(|| format!("cannot back up {} to {}", path, backup.display()))?;
+        info!(backup = %backup.display(), "backed up database before upgrading");
+    }
+
+    let db = sled::open(path)?;
+    for migration in MIGRATIONS.iter().filter(|migration| migration.to > version) {
+        info!(to = migration.to, "migrating database: {}", migration.description);
+        (migration.run)(&db)
+            .with_context(|| format!("migration to schema version {} failed", migration.to))?;
+        set_version(&db, migration.to)?;
+        db.flush()?;
+    }
+    Ok(db)
+}
+
+/// The schema version recorded in `db`, or `None` when it has no marker.
+pub fn stored_version(db: &Db) -> Result<Option<u32>> {
+    match db.open_tree(META_TREE)?.get(VERSION_KEY)? {
+        Some(value) => {
+            let bytes: [u8; 4] = value.as_ref().try_into().context("corrupt schema version")?;
+            Ok(Some(u32::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn set_version(db: &Db, version: u32) -> Result<()> {
+    db.open_tree(META_TREE)?.insert(VERSION_KEY, &version.to_be_bytes())?;
+    Ok(())
+}
+
+/// Where the copy of a database at `version` is kept before upgrading,
+/// such as `index.db.v1.bak` for `index.db`.
+pub fn backup_path(db: &Path, version: u32) -> PathBuf {
+    let mut name = db.as_os_str().to_owned();
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+fn is_empty(db: &Db) -> Result<bool> {
+    for name in db.tree_names() {
+        if db.open_tree(&name)?.first()?.is_some() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 1: fills the path tree from the file records when it is missing
+/// and rewrites roots stored as a bare completion time as full records.
+fn paths_and_roots(db: &Db) -> Result<()> {
+    let paths = db.open_tree("paths")?;
+    if paths.is_empty() {
+        for item in db.iter() {
+            let (key, value) = item?;
+            if let Ok(file_info) = record::decode_without_content(&value) {
+                paths.insert(models::path_bytes(&file_info.os_path()), key)?;
+            }
+        }
+        info!(files = paths.len(), "rebuilt path index");
+    }
+    let roots = db.open_tree("roots")?;
+    for item in roots.iter() {
+        let (key, value) = item?;
+        if serde_json::from_slice::<models::Root>(&value).is_err() {
+            let root = crate::indexer::decode_root(&key, &value);
+            roots.insert(key, serde_json::to_vec(&root)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 2: rewrites JSON file records in the binary layout.
+fn binary_records(db: &Db) -> Result<()> {
+    let mut converted = 0u64;
+    for item in db.iter() {
+        let (key, value) = item?;
+        if value.first() == Some(&b'{') {
+            db.insert(key, record::encode(&record::decode(&value)?))?;
+            converted += 1;
+        }
+    }
+    info!(files = converted, "re-encoded file records");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use crate::FileInfo;
+
+    fn temp_db(name: &str) -> (TempDir, String) {
+        let dir = TempDir::new(&format!("schema-{}", name));
+        let db = dir.db();
+        (dir, db)
+    }
+
+    #[test]
+    fn test_new_database_is_stamped() {
+        let (_dir, path) = temp_db("new");
+        let db = open(&path).unwrap();
+        assert_eq!(stored_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        drop(db);
+        assert!(!backup_path(Path::new(&path), 0).exists());
+    }
+
+    #[test]
+    fn test_upgrade_unversioned_database() {
+        let (_dir, path) = temp_db("upgrade");
+        let file = FileInfo {
+            id: "abc".to_string(),
+            path: "/srv/data/notes.txt".to_string(),
+            size: 5,
+            content: Some("hello".to_string()),
+            extension: "txt".to_string(),
+            created_at: "2024-01-19T00:00:00+00:00".to_string(),
+            modified_at: "2024-01-19T00:00:00+00:00".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: Default::default(),
+            partial_hash: None,
+        };
+        {
+            let db = sled::open(&path).unwrap();
+            db.insert("abc", serde_json::to_vec(&file).unwrap()).unwrap();
+            db.open_tree("roots").unwrap().insert("/srv/data", "2024-01-19T00:00:00+00:00").unwrap();
+            db.flush().unwrap();
+        }
+
+        let db = open(&path).unwrap();
+        assert_eq!(stored_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        let stored = db.get("abc").unwrap().unwrap();
+        assert_eq!(stored[0], record::RECORD_VERSION);
+        assert_eq!(record::decode(&stored).unwrap().content.as_deref(), Some("hello"));
+        assert_eq!(db.open_tree("paths").unwrap().get("/srv/data/notes.txt").unwrap().unwrap(), "abc");
+        let root: models::Root =
+            serde_json::from_slice(&db.open_tree("roots").unwrap().get("/srv/data").unwrap().unwrap()).unwrap();
+        assert_eq!(root.name, "data");
+        drop(db);
+
+        let backup = sled::open(backup_path(Path::new(&path), 0)).unwrap();
+        assert_eq!(stored_version(&backup).unwrap(), None);
+        assert_eq!(backup.get("abc").unwrap().unwrap()[0], b'{');
+        drop(backup);
+    }
+
+    #[test]
+    fn test_newer_database_is_refused() {
+        let (_dir, path) = temp_db("newer");
+        {
+            let db = sled::open(&path).unwrap();
+            set_version(&db, SCHEMA_VERSION + 1).unwrap();
+            db.flush().unwrap();
+        }
+        let error = open(&path).unwrap_err().to_string();
+        assert!(error.contains(&format!("schema version {}", SCHEMA_VERSION + 1)), "{}", error);
+        assert!(error.contains("upgrade rust-search"), "{}", error);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::models::FileInfo;
 
+#[derive(Default)]
 pub struct SearchEngine;
 
 impl SearchEngine {
@@ -44,6 +45,31 @@ impl SearchEngine {
         0.0
     }
 
+    /// Scores a path by fuzzy subsequence match, for search-as-you-type.
+    ///
+    /// Exact and substring hits keep their usual scores. Otherwise the query
+    /// characters must appear in order: hits inside the file name score up to
+    /// 45, hits spread over the directory part up to 25. Consecutive
+    /// characters and matches at word boundaries score higher.
+    pub fn search_fuzzy(&self, file: &FileInfo, query: &str, case_sensitive: bool) -> f32 {
+        let exact = self.search_filename(file, query, case_sensitive);
+        if exact > 0.0 {
+            return exact;
+        }
+
+        let file_name = file
+            .path
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(&file.path);
+
+        let name_score = fuzzy_match(file_name, query, case_sensitive);
+        if name_score > 0.0 {
+            return 25.0 + name_score * 20.0;
+        }
+        fuzzy_match(&file.path, query, case_sensitive) * 25.0
+    }
+
     pub fn search_content(&self, file: &FileInfo, query: &str, case_sensitive: bool) -> f32 {
         let content = match &file.content {
             Some(c) => c,
@@ -69,8 +95,7 @@ impl SearchEngine {
         }
 
         // Score based on frequency
-        let score = (count as f32 * 10.0).min(95.0);
-        score
+        (count as f32 * 10.0).min(95.0)
     }
 
     pub fn extract_preview(
@@ -106,6 +131,72 @@ impl SearchEngine {
 
         None
     }
+
+    /// Returns the byte ranges of every occurrence of `query` in `content`.
+    pub fn find_matches(
+        &self,
+        content: &str,
+        query: &str,
+        case_sensitive: bool,
+    ) -> Vec<(usize, usize)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        if case_sensitive {
+            return content.match_indices(query).map(|(pos, m)| (pos, pos + m.len())).collect();
+        }
+
+        // Lowercasing can change a character's byte width (`İ` becomes `i̇`),
+        // so remember where each lowered character starts in `content` and
+        // drop matches that begin or end inside one.
+        let mut lower = String::with_capacity(content.len());
+        let mut origin = Vec::with_capacity(content.len() + 1);
+        for (pos, c) in content.char_indices() {
+            origin.push(Some(pos));
+            lower.extend(c.to_lowercase());
+            origin.resize(lower.len(), None);
+        }
+        origin.push(Some(content.len()));
+
+        lower
+            .match_indices(&query.to_lowercase())
+            .filter_map(|(pos, m)| Some((origin[pos]?, origin[pos + m.len()]?)))
+            .collect()
+    }
+}
+
+/// Matches `query` as a subsequence of `text`, returning a quality in
+/// `(0, 1]`, or 0 when some query character is missing.
+fn fuzzy_match(text: &str, query: &str, case_sensitive: bool) -> f32 {
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let text: Vec<char> = text.chars().map(fold).collect();
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(fold).collect();
+    if query.is_empty() {
+        return 0.0;
+    }
+
+    let mut points = 0.0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+    for qc in &query {
+        let found = match text[pos..].iter().position(|c| c == qc) {
+            Some(offset) => pos + offset,
+            None => return 0.0,
+        };
+
+        points += 1.0;
+        if found > 0 && previous == Some(found - 1) {
+            points += 2.0;
+        }
+        if found == 0 || matches!(text[found - 1], '/' | '\\' | '_' | '-' | '.' | ' ') {
+            points += 1.5;
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+
+    points / (query.len() as f32 * 4.5)
 }
 
 #[cfg(test)]
@@ -122,6 +213,10 @@ mod tests {
             extension: "rs".to_string(),
             created_at: "2024-01-19".to_string(),
             modified_at: "2024-01-19".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: Default::default(),
+            partial_hash: None,
         };
 
         let engine = SearchEngine::new();
@@ -139,9 +234,59 @@ mod tests {
             extension: "rs".to_string(),
             created_at: "2024-01-19".to_string(),
             modified_at: "2024-01-19".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: Default::default(),
+            partial_hash: None,
         };
 
         let engine = SearchEngine::new();
         assert!(engine.search_content(&file, "fn", false) > 0.0);
     }
+
+    #[test]
+    fn test_search_fuzzy() {
+        let file = FileInfo {
+            id: "test".to_string(),
+            path: "/home/project/src/search_engine.rs".to_string(),
+            size: 100,
+            content: None,
+            extension: "rs".to_string(),
+            created_at: "2024-01-19".to_string(),
+            modified_at: "2024-01-19".to_string(),
+            path_bytes: None,
+            truncated: false,
+            metadata: Default::default(),
+            partial_hash: None,
+        };
+
+        let engine = SearchEngine::new();
+        let name_hit = engine.search_fuzzy(&file, "srchng", false);
+        let dir_hit = engine.search_fuzzy(&file, "prjsrc", false);
+        assert!(name_hit > 25.0 && name_hit <= 45.0);
+        assert!(dir_hit > 0.0 && dir_hit <= 25.0);
+        assert_eq!(engine.search_fuzzy(&file, "xyz", false), 0.0);
+        assert_eq!(engine.search_fuzzy(&file, "engine", false), 75.0);
+        assert!(engine.search_fuzzy(&file, "se", false) > engine.search_fuzzy(&file, "sn", false));
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let engine = SearchEngine::new();
+        let content = "Main calls main, then MAIN";
+
+        assert_eq!(
+            engine.find_matches(content, "main", false),
+            vec![(0, 4), (11, 15), (22, 26)]
+        );
+        assert_eq!(engine.find_matches(content, "main", true), vec![(11, 15)]);
+        assert!(engine.find_matches(content, "", false).is_empty());
+
+        // `İ` grows and `ẞ` shrinks when lowered, keeping the total length.
+        let content = "İẞ Main";
+        assert_eq!(content.to_lowercase().len(), content.len());
+        assert_eq!(engine.find_matches(content, "main", false), vec![(6, 10)]);
+        assert_eq!(engine.find_matches(content, "i̇ß", false), vec![(0, 5)]);
+        assert_eq!(engine.find_matches(content, "i", false), vec![(8, 9)]);
+    }
 }
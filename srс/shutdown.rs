@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Completes when the process receives SIGINT (Ctrl-C) or SIGTERM.
+pub async fn wait() {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+/// Returns a flag that is raised on the first shutdown signal.
+///
+/// Long-running work polls the flag to stop at a safe point. A second
+/// signal exits immediately for users who do not want to wait.
+pub fn stop_flag() -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let raised = stop.clone();
+    tokio::spawn(async move {
+        wait().await;
+        warn!("shutdown requested, finishing current work (signal again to force)");
+        raised.store(true, Ordering::Relaxed);
+        wait().await;
+        std::process::exit(130);
+    });
+    stop
+}
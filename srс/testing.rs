@@ -0,0 +1,53 @@
+//! Fixtures shared by the unit tests.
+
+use crate::models::SearchFilter;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory under the system temp dir, emptied on creation and
+/// removed when dropped, even if the test panics.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates `rust-search-{name}-{pid}`; the name keeps tests running in
+    /// parallel apart.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rust-search-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    /// Path of a database inside the directory, as taken by [`crate::Indexer::new`].
+    pub fn db(&self) -> String {
+        self.path.join("db").to_str().unwrap().to_string()
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A filter matching `query` in names and content, without size or count limits
+/// getting in the way.
+pub fn content_filter(query: &str) -> SearchFilter {
+    SearchFilter {
+        query: query.to_string(),
+        search_content: true,
+        max_size: u64::MAX,
+        limit: 10,
+        ..Default::default()
+    }
+}
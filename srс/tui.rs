@@ -0,0 +1,384 @@
+use crate::actions;
+use crate::search_engine::SearchEngine;
+use crate::{Indexer, SearchFilter, SearchResult};
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const RESULT_LIMIT: usize = 200;
+const PAGE_SIZE: usize = 10;
+
+/// Where queries are sent: the database itself, or the daemon that owns it.
+enum Source {
+    Local(Box<Indexer>),
+    #[cfg(all(unix, feature = "daemon"))]
+    Daemon(String),
+}
+
+impl Source {
+    fn open(db: &str) -> Result<Self> {
+        #[cfg(all(unix, feature = "daemon"))]
+        if crate::daemon::Client::connect(db).is_some() {
+            return Ok(Source::Daemon(db.to_string()));
+        }
+        Ok(Source::Local(Box::new(Indexer::new(db)?)))
+    }
+
+    /// Returns `None` when the search was cancelled by a newer query.
+    fn search(&self, filter: &SearchFilter, cancel: &AtomicBool) -> Result<Option<Vec<SearchResult>>> {
+        match self {
+            Source::Local(indexer) => indexer.search_with_cancel(filter, cancel),
+            #[cfg(all(unix, feature = "daemon"))]
+            Source::Daemon(db) => {
+                let mut client = crate::daemon::Client::connect(db)
+                    .ok_or_else(|| anyhow::anyhow!("daemon for {} is no longer running", db))?;
+                let results = client.search(filter)?;
+                Ok((!cancel.load(Ordering::Relaxed)).then_some(results))
+            }
+        }
+    }
+}
+
+/// A finished search, tagged with the query generation it answers.
+struct SearchReply {
+    generation: u64,
+    results: Result<Option<Vec<SearchResult>>>,
+}
+
+struct App {
+    source: Arc<Source>,
+    query: String,
+    search_content: bool,
+    results: Vec<SearchResult>,
+    list: ListState,
+    generation: u64,
+    cancel: Arc<AtomicBool>,
+    searching: bool,
+    status: Option<String>,
+    replies: Sender<SearchReply>,
+}
+
+impl App {
+    /// Starts a search for the current query on a worker thread, cancelling
+    /// the previous one so typing never waits on a stale scan.
+    fn refresh(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.generation += 1;
+        self.status = None;
+
+        if self.query.trim().is_empty() {
+            self.results.clear();
+            self.list.select(None);
+            self.searching = false;
+            return;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.cancel = cancel.clone();
+        self.searching = true;
+
+        let filter = SearchFilter {
+            query: self.query.clone(),
+            search_content: self.search_content,
+            fuzzy: !self.search_content,
+            limit: RESULT_LIMIT,
+            max_size: u64::MAX,
+            ..Default::default()
+        };
+        let source = self.source.clone();
+        let replies = self.replies.clone();
+        let generation = self.generation;
+        thread::spawn(move || {
+            let results = source.search(&filter, &cancel);
+            let _ = replies.send(SearchReply { generation, results });
+        });
+    }
+
+    fn receive(&mut self, reply: SearchReply) {
+        if reply.generation != self.generation {
+            return;
+        }
+        self.searching = false;
+        match reply.results {
+            Ok(Some(results)) => {
+                self.results = results;
+                self.list.select((!self.results.is_empty()).then_some(0));
+            }
+            Ok(None) => {}
+            Err(e) => self.status = Some(format!("search failed: {}", e)),
+        }
+    }
+
+    fn selected(&self) -> Option<&SearchResult> {
+        self.list.selected().and_then(|i| self.results.get(i))
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.results.is_empty() {
+            return;
+        }
+        let last = self.results.len() as isize - 1;
+        let current = self.list.selected().unwrap_or(0) as isize;
+        self.list.select(Some((current + delta).clamp(0, last) as usize));
+    }
+}
+
+/// Runs the full-screen interface until the user quits.
+///
+/// `initial_query` pre-fills the query box, e.g. from `rust-search -i main`.
+pub fn run(db: &str, initial_query: &str) -> Result<()> {
+    let source = Arc::new(Source::open(db)?);
+    let (replies, incoming) = mpsc::channel();
+    let mut app = App {
+        source,
+        query: initial_query.to_string(),
+        search_content: false,
+        results: Vec::new(),
+        list: ListState::default(),
+        generation: 0,
+        cancel: Arc::new(AtomicBool::new(false)),
+        searching: false,
+        status: None,
+        replies,
+    };
+    app.refresh();
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &incoming);
+    ratatui::restore();
+    app.cancel.store(true, Ordering::Relaxed);
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    incoming: &Receiver<SearchReply>,
+) -> Result<()> {
+    loop {
+        while let Ok(reply) = incoming.try_recv() {
+            app.receive(reply);
+        }
+        terminal.draw(|frame| draw(frame, app))?;
+
+        if !event::poll(Duration::from_millis(50))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if !handle_key(terminal, app, key)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Applies one key press. Returns `false` when the user asked to quit.
+fn handle_key(terminal: &mut DefaultTerminal, app: &mut App, key: KeyEvent) -> Result<bool> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => return Ok(false),
+        KeyCode::Char('c') if ctrl => return Ok(false),
+        KeyCode::Up => app.move_by(-1),
+        KeyCode::Char('p') if ctrl => app.move_by(-1),
+        KeyCode::Down => app.move_by(1),
+        KeyCode::Char('n') if ctrl => app.move_by(1),
+        KeyCode::PageUp => app.move_by(-(PAGE_SIZE as isize)),
+        KeyCode::PageDown => app.move_by(PAGE_SIZE as isize),
+        KeyCode::Tab => {
+            app.search_content = !app.search_content;
+            app.refresh();
+        }
+        KeyCode::Char('u') if ctrl => {
+            app.query.clear();
+            app.refresh();
+        }
+        KeyCode::Char('y') if ctrl => {
+            if let Some(path) = app.selected().map(|r| r.path.clone()) {
+                app.status = Some(match actions::copy_to_clipboard(&path) {
+                    Ok(()) => format!("copied {}", path),
+                    Err(e) => format!("copy failed: {}", e),
+                });
+            }
+        }
+        KeyCode::Enter => {
+            if let Some(result) = app.selected().cloned() {
+                let path = crate::archive::on_disk(&result.os_path());
+                let line = app
+                    .search_content
+                    .then(|| actions::first_match_line(&path, &app.query, false))
+                    .flatten();
+                let opened = suspended(terminal, || actions::open_in_editor(&path, line))?;
+                if let Err(e) = opened {
+                    app.status = Some(e.to_string());
+                }
+            }
+        }
+        KeyCode::Backspace if app.query.pop().is_some() => app.refresh(),
+        KeyCode::Char(c) if !ctrl => {
+            app.query.push(c);
+            app.refresh();
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Hands the terminal to a child process (the editor) and takes it back.
+fn suspended<T>(terminal: &mut DefaultTerminal, f: impl FnOnce() -> T) -> Result<T> {
+    disable_raw_mode()?;
+    execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    let value = f();
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+    Ok(value)
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(1)])
+        .split(frame.area());
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[1]);
+
+    draw_query(frame, app, rows[0]);
+    draw_results(frame, app, panes[0]);
+    draw_preview(frame, app, panes[1]);
+    draw_status(frame, app, rows[2]);
+}
+
+fn draw_query(frame: &mut Frame, app: &App, area: Rect) {
+    let mode = if app.search_content { "contents" } else { "names (fuzzy)" };
+    let title = format!(" 🔍 rust-search: {} ", mode);
+    let input = Paragraph::new(app.query.as_str())
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(input, area);
+
+    let cursor = app.query.chars().count() as u16;
+    frame.set_cursor_position((
+        (area.x + 1 + cursor).min(area.right().saturating_sub(2)),
+        area.y + 1,
+    ));
+}
+
+fn draw_results(frame: &mut Frame, app: &mut App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .results
+        .iter()
+        .map(|r| ListItem::new(r.path.as_str()))
+        .collect();
+    let title = if app.searching {
+        " searching… ".to_string()
+    } else {
+        format!(" {} results ", app.results.len())
+    };
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, area, &mut app.list);
+}
+
+fn draw_preview(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(" Preview ");
+    let Some(result) = app.selected() else {
+        frame.render_widget(block, area);
+        return;
+    };
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            result.path.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!(
+            "Score: {:.1} | Size: {} KB | Modified: {}",
+            result.score,
+            result.size / 1024,
+            result.modified_at
+        )),
+        Line::default(),
+    ];
+    match &result.matched_content {
+        Some(preview) => lines.push(highlight(preview, &app.query)),
+        None => lines.push(Line::from(Span::styled(
+            "No content preview",
+            Style::default().fg(Color::DarkGray),
+        ))),
+    }
+
+    let preview = Paragraph::new(lines).block(block).wrap(Wrap { trim: false });
+    frame.render_widget(preview, area);
+}
+
+/// Splits `text` into spans with every occurrence of `query` highlighted.
+fn highlight<'a>(text: &'a str, query: &str) -> Line<'a> {
+    let mark = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for (start, end) in SearchEngine::new().find_matches(text, query, false) {
+        if start > pos {
+            spans.push(Span::raw(&text[pos..start]));
+        }
+        spans.push(Span::styled(&text[start..end], mark));
+        pos = end;
+    }
+    if pos < text.len() {
+        spans.push(Span::raw(&text[pos..]));
+    }
+    Line::from(spans)
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let text = app.status.clone().unwrap_or_else(|| {
+        "↑/↓ move · Enter open in $EDITOR · Ctrl-Y copy path · Tab names/contents · Ctrl-U clear · Esc quit"
+            .to_string()
+    });
+    let status = Paragraph::new(text).style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(status, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_spans() {
+        let line = highlight("fn main() { main_loop() }", "main");
+        let parts: Vec<(&str, bool)> = line
+            .spans
+            .iter()
+            .map(|s| (s.content.as_ref(), s.style.bg == Some(Color::Yellow)))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                ("fn ", false),
+                ("main", true),
+                ("() { ", false),
+                ("main", true),
+                ("_loop() }", false),
+            ]
+        );
+    }
+}
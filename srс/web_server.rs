@@ -1,14 +1,46 @@
 use axum::{
     routing::{get, post},
     Router, Json, extract::State,
+    extract::{Path, Query},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    body::Body,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode},
+    middleware,
+    response::{Html, IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use crate::{Indexer, SearchFilter};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+use tokio_util::io::ReaderStream;
+use tower::ServiceBuilder;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::request_id::{MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::{error, info, warn, Level};
+use crate::{shutdown, DuplicateFilter, FileInfo, IndexOptions, IndexSettings, Indexer, SearchFilter};
+use crate::auth::{require_role, ApiKeys, AuthLayerState, Role, ADMIN_KEYS_ENV, READ_KEYS_ENV};
+use crate::metrics::{self, IndexGauges, QueryMetrics};
+use crate::search_engine::SearchEngine;
+
+/// Largest amount of stored content rendered by the preview endpoint.
+const PREVIEW_LIMIT: usize = 256 * 1024;
+
+/// Largest archive member served by the content endpoint, which reads
+/// members into memory.
+const MEMBER_LIMIT: u64 = 64 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct AppState {
     pub indexer: Arc<Indexer>,
+    pub metrics: Arc<QueryMetrics>,
+    /// Raised on shutdown so background indexing checkpoints and stops.
+    pub stop: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,6 +49,97 @@ pub struct SearchRequest {
     pub all: Option<bool>,
     pub ext: Option<String>,
     pub limit: Option<usize>,
+    /// Absolute directory that results must lie under.
+    pub path_prefix: Option<String>,
+}
+
+impl SearchRequest {
+    /// Builds the filter, failing with a message for the client when the
+    /// request is invalid.
+    pub fn to_filter(&self) -> Result<SearchFilter, String> {
+        Ok(SearchFilter {
+            query: self.query.clone(),
+            search_content: self.all.unwrap_or(false),
+            extensions: self
+                .ext
+                .as_deref()
+                .map(|ext| ext.split(',').map(|s| s.trim().to_string()).collect())
+                .unwrap_or_default(),
+            max_size: u64::MAX,
+            limit: self.limit.unwrap_or(100),
+            path_prefix: absolute_prefix(self.path_prefix.as_deref())?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses a `path_prefix` parameter. Relative paths are refused: they would
+/// resolve against the server's working directory, which clients cannot see.
+fn absolute_prefix(prefix: Option<&str>) -> Result<Option<PathBuf>, String> {
+    match prefix.map(PathBuf::from) {
+        Some(path) if !path.is_absolute() => {
+            Err(format!("path_prefix must be an absolute path, got `{}`", path.display()))
+        }
+        path => Ok(path),
+    }
+}
+
+/// A query update sent by a live search client over `/api/ws`.
+///
+/// `id` is chosen by the client and echoed back so it can discard replies
+/// to queries it has already replaced.
+#[derive(Debug, Deserialize)]
+pub struct LiveSearchRequest {
+    pub id: u64,
+    #[serde(flatten)]
+    pub search: SearchRequest,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LiveSearchResponse {
+    pub id: u64,
+    pub success: bool,
+    pub data: Option<Vec<crate::SearchResult>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileMetadata {
+    pub file_id: String,
+    pub path: String,
+    pub size: u64,
+    pub extension: String,
+    pub content_type: String,
+    pub has_content: bool,
+    pub truncated: bool,
+    pub created_at: String,
+    pub modified_at: String,
+}
+
+/// Query parameters of `/api/duplicates`.
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesParams {
+    /// Absolute directory that compared files must lie under.
+    pub path_prefix: Option<String>,
+    /// Smallest file size compared, in bytes.
+    pub min_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexRequest {
+    pub path: PathBuf,
+    pub resume: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentParams {
+    pub download: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewParams {
+    pub q: Option<String>,
+    pub case_sensitive: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -26,49 +149,243 @@ pub struct ApiResponse<T> {
     pub error: Option<String>,
 }
 
-pub async fn start_server(host: &str, port: u16, db: &str) -> anyhow::Result<()> {
-    let indexer = Arc::new(Indexer::new(db)?);
-    let state = AppState { indexer };
+/// Settings for [`start_server`].
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub db: String,
+    /// Key file with `<role> <key>` lines; see [`ApiKeys::load`].
+    pub auth_file: Option<PathBuf>,
+    /// Origins allowed to call the API from a browser; `*` allows any.
+    /// Empty disables CORS so only same-origin pages can use the API.
+    pub cors_origins: Vec<String>,
+    /// Excludes and text extensions used by `/api/index`.
+    pub index: IndexSettings,
+}
+
+pub async fn start_server(config: ServerConfig) -> anyhow::Result<()> {
+    let keys = Arc::new(ApiKeys::load(config.auth_file.as_deref())?);
+    warn_if_exposed(&config.host, &keys);
+    let cors = cors_layer(&config.cors_origins)?;
 
-    let app = Router::new()
-        .route("/", get(handler_root))
+    let indexer = Arc::new(Indexer::with_settings(&config.db, config.index)?);
+    let stop = Arc::new(AtomicBool::new(false));
+    let state = AppState {
+        indexer: indexer.clone(),
+        metrics: Arc::new(QueryMetrics::default()),
+        stop: stop.clone(),
+    };
+
+    let read_routes = Router::new()
         .route("/api/search", post(handler_search))
         .route("/api/stats", get(handler_stats))
+        .route("/api/duplicates", get(handler_duplicates))
+        .route("/api/ws", get(handler_ws))
+        .route("/api/files/:file_id", get(handler_file))
+        .route("/api/files/:file_id/content", get(handler_file_content))
+        .route("/api/files/:file_id/preview", get(handler_file_preview))
+        .route("/metrics", get(handler_metrics))
+        .route_layer(middleware::from_fn_with_state(
+            AuthLayerState { keys: keys.clone(), required: Role::ReadOnly },
+            require_role,
+        ));
+
+    let admin_routes = Router::new()
+        .route("/api/index", post(handler_index))
+        .route("/api/vacuum", post(handler_vacuum))
+        .route("/api/clear", post(handler_clear))
+        .route_layer(middleware::from_fn_with_state(
+            AuthLayerState { keys, required: Role::Admin },
+            require_role,
+        ));
+
+    let app = Router::new()
+        .route("/", get(handler_root))
+        .route("/healthz", get(handler_healthz))
+        .route("/readyz", get(handler_readyz))
+        .merge(read_routes)
+        .merge(admin_routes)
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
-    println!("Soon.....");
-    
-    axum::serve(listener, app).await?;
+    let app = match cors {
+        Some(cors) => app.layer(cors),
+        None => app,
+    };
+
+    let app = app.layer(
+        ServiceBuilder::new()
+            .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER.clone(), RequestIdGenerator::default()))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(request_span)
+                    .on_response(
+                        DefaultOnResponse::new()
+                            .level(Level::INFO)
+                            .latency_unit(LatencyUnit::Millis),
+                    ),
+            )
+            .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER.clone())),
+    );
+
+    let listener = tokio::net::TcpListener::bind(format!("{}:{}", config.host, config.port)).await?;
+    info!(address = %listener.local_addr()?, "server listening");
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown::wait())
+        .await?;
+
+    // Background indexing runs on blocking threads, which the runtime waits
+    // for on exit; ask them to checkpoint and stop, then flush.
+    info!("server shutting down");
+    stop.store(true, Ordering::Relaxed);
+    indexer.vacuum()?;
     Ok(())
 }
 
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+fn warn_if_exposed(host: &str, keys: &ApiKeys) {
+    if keys.is_enabled() {
+        return;
+    }
+    if is_loopback(host) {
+        warn!("no API keys configured: admin endpoints are open to local users");
+    } else {
+        warn!(
+            host,
+            "serving without API keys - anyone who can reach this address can read \
+             indexed files and clear the database; set {} / {} or pass --auth-file",
+            READ_KEYS_ENV,
+            ADMIN_KEYS_ENV
+        );
+    }
+}
+
+static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Hands out request IDs unique to this server process.
+///
+/// IDs combine the process start time with a counter, so they stay unique
+/// across restarts without pulling in a UUID generator. A client-supplied
+/// `x-request-id` is kept as-is.
+#[derive(Clone)]
+struct RequestIdGenerator {
+    prefix: Arc<str>,
+    next: Arc<AtomicU64>,
+}
+
+impl Default for RequestIdGenerator {
+    fn default() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        Self {
+            prefix: format!("{:x}", started).into(),
+            next: Arc::new(AtomicU64::new(1)),
+        }
+    }
+}
+
+impl MakeRequestId for RequestIdGenerator {
+    fn make_request_id<B>(&mut self, _request: &Request<B>) -> Option<RequestId> {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        HeaderValue::from_str(&format!("{}-{}", self.prefix, id))
+            .ok()
+            .map(RequestId::new)
+    }
+}
+
+/// Opens the span every log line of a request is recorded under.
+///
+/// Only the path is logged: the query string may carry an `api_key`.
+fn request_span<B>(request: &Request<B>) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    )
+}
+
+fn cors_layer(origins: &[String]) -> anyhow::Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| HeaderValue::from_str(o))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::RANGE,
+                HeaderName::from_static("x-api-key"),
+            ])
+            .expose_headers([
+                REQUEST_ID_HEADER.clone(),
+                header::CONTENT_RANGE,
+                header::ACCEPT_RANGES,
+            ]),
+    ))
+}
+
 async fn handler_root() -> &'static str {
-    "🌐 rust-search API v0.2.0 - Use POST /api/search or GET /api/stats"
+    "🌐 rust-search API v0.2.0 - Use POST /api/search, GET /api/stats, GET /api/duplicates or WS /api/ws"
 }
 
 async fn handler_search(
     State(state): State<AppState>,
     Json(payload): Json<SearchRequest>,
-) -> Json<ApiResponse<Vec<crate::SearchResult>>> {
-    let filter = SearchFilter {
-        query: payload.query,
-        search_content: payload.all.unwrap_or(false),
-        limit: payload.limit.unwrap_or(100),
-        ..Default::default()
+) -> Response {
+    let filter = match payload.to_filter() {
+        Ok(filter) => filter,
+        Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
     };
 
-    match state.indexer.search(&filter) {
+    let started = Instant::now();
+    let outcome = state.indexer.search(&filter);
+    state
+        .metrics
+        .record(started.elapsed(), outcome.as_ref().ok().map(Vec::len));
+
+    match outcome {
         Ok(results) => Json(ApiResponse {
             success: true,
             data: Some(results),
             error: None,
-        }),
-        Err(e) => Json(ApiResponse {
+        })
+        .into_response(),
+        Err(e) => Json(ApiResponse::<()> {
             success: false,
             data: None,
             error: Some(e.to_string()),
-        }),
+        })
+        .into_response(),
     }
 }
 
@@ -88,3 +405,760 @@ async fn handler_stats(
         }),
     }
 }
+
+async fn handler_duplicates(
+    State(state): State<AppState>,
+    Query(params): Query<DuplicatesParams>,
+) -> Response {
+    let filter = match absolute_prefix(params.path_prefix.as_deref()) {
+        Ok(path_prefix) => DuplicateFilter { path_prefix, min_size: params.min_size.unwrap_or(0) },
+        Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
+    };
+    // Candidates are hashed from disk, so keep that off the request workers.
+    let indexer = state.indexer.clone();
+    match tokio::task::spawn_blocking(move || indexer.duplicates(&filter)).await {
+        Ok(Ok(report)) => Json(ApiResponse {
+            success: true,
+            data: Some(report),
+            error: None,
+        })
+        .into_response(),
+        Ok(Err(e)) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn handler_healthz() -> &'static str {
+    "ok"
+}
+
+async fn handler_readyz(State(state): State<AppState>) -> Response {
+    if state.indexer.is_ready() {
+        "ready".into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "database unavailable").into_response()
+    }
+}
+
+async fn handler_metrics(State(state): State<AppState>) -> Response {
+    let gauges = IndexGauges {
+        documents: state.indexer.document_count(),
+        disk_bytes: state.indexer.size_on_disk().unwrap_or(0),
+        last_indexed: state
+            .indexer
+            .last_indexed_at()
+            .ok()
+            .flatten()
+            .map(|t| t.timestamp()),
+    };
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&state.metrics, state.indexer.metrics(), &gauges),
+    )
+        .into_response()
+}
+
+async fn handler_index(
+    State(state): State<AppState>,
+    Json(payload): Json<IndexRequest>,
+) -> Response {
+    if !payload.path.is_dir() {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            format!("{} is not a directory", payload.path.display()),
+        );
+    }
+
+    // Indexing walks the filesystem synchronously, so keep it off the
+    // request workers and answer immediately.
+    let indexer = state.indexer.clone();
+    let path = payload.path.clone();
+    let resume = payload.resume.unwrap_or(false);
+    let stop = state.stop.clone();
+    let runtime = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let options = IndexOptions {
+            resume,
+            stop: Some(&stop),
+            ..Default::default()
+        };
+        if let Err(e) = runtime.block_on(indexer.index_dir_with(&path, &options)) {
+            error!(path = %path.display(), error = %e, "indexing failed");
+        }
+    });
+
+    (
+        StatusCode::ACCEPTED,
+        Json(ApiResponse {
+            success: true,
+            data: Some(format!("indexing {} started", payload.path.display())),
+            error: None,
+        }),
+    )
+        .into_response()
+}
+
+async fn handler_vacuum(State(state): State<AppState>) -> Response {
+    match state.indexer.vacuum() {
+        Ok(()) => Json(ApiResponse::<()> { success: true, data: None, error: None }).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn handler_clear(State(state): State<AppState>) -> Response {
+    match state.indexer.clear() {
+        Ok(()) => Json(ApiResponse::<()> { success: true, data: None, error: None }).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn handler_ws(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| live_search(socket, state))
+}
+
+/// Drives one live search connection.
+///
+/// Every incoming query cancels the one still running, so fast typing only
+/// ever keeps a single scan alive per client. Replies for superseded queries
+/// are dropped before they reach the socket.
+async fn live_search(mut socket: WebSocket, state: AppState) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<LiveSearchResponse>();
+    let mut running: Option<Arc<AtomicBool>> = None;
+    let mut latest_id = None;
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let request: LiveSearchRequest = match serde_json::from_str(&text) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        // Answered directly: the reply belongs to no running
+                        // query, so it must not go through the id filter below.
+                        let id = serde_json::from_str::<serde_json::Value>(&text)
+                            .ok()
+                            .and_then(|value| value.get("id")?.as_u64())
+                            .or(latest_id)
+                            .unwrap_or_default();
+                        let response = LiveSearchResponse {
+                            id,
+                            success: false,
+                            data: None,
+                            error: Some(format!("invalid query: {}", e)),
+                        };
+                        if !send_live_response(&mut socket, &response).await {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                if let Some(previous) = running.take() {
+                    previous.store(true, Ordering::Relaxed);
+                }
+                latest_id = Some(request.id);
+
+                let filter = match request.search.to_filter() {
+                    Ok(filter) => filter,
+                    Err(message) => {
+                        let _ = tx.send(LiveSearchResponse {
+                            id: request.id,
+                            success: false,
+                            data: None,
+                            error: Some(message),
+                        });
+                        continue;
+                    }
+                };
+                if filter.query.trim().is_empty() {
+                    let _ = tx.send(LiveSearchResponse {
+                        id: request.id,
+                        success: true,
+                        data: Some(Vec::new()),
+                        error: None,
+                    });
+                    continue;
+                }
+
+                let cancel = Arc::new(AtomicBool::new(false));
+                running = Some(cancel.clone());
+
+                let indexer = state.indexer.clone();
+                let metrics = state.metrics.clone();
+                let tx = tx.clone();
+                tokio::task::spawn_blocking(move || {
+                    let started = Instant::now();
+                    let outcome = indexer.search_with_cancel(&filter, &cancel);
+                    match &outcome {
+                        Ok(Some(results)) => metrics.record(started.elapsed(), Some(results.len())),
+                        Ok(None) => {}
+                        Err(_) => metrics.record(started.elapsed(), None),
+                    }
+                    let response = match outcome {
+                        Ok(Some(results)) => LiveSearchResponse {
+                            id: request.id,
+                            success: true,
+                            data: Some(results),
+                            error: None,
+                        },
+                        Ok(None) => return,
+                        Err(e) => LiveSearchResponse {
+                            id: request.id,
+                            success: false,
+                            data: None,
+                            error: Some(e.to_string()),
+                        },
+                    };
+                    let _ = tx.send(response);
+                });
+            }
+
+            Some(response) = rx.recv() => {
+                if Some(response.id) != latest_id {
+                    continue;
+                }
+                if !send_live_response(&mut socket, &response).await {
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(cancel) = running {
+        cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sends one reply over a live search socket; `false` once the client is gone.
+async fn send_live_response(socket: &mut WebSocket, response: &LiveSearchResponse) -> bool {
+    match serde_json::to_string(response) {
+        Ok(json) => socket.send(Message::Text(json)).await.is_ok(),
+        Err(_) => true,
+    }
+}
+
+pub(crate) fn api_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(ApiResponse::<()> {
+            success: false,
+            data: None,
+            error: Some(message.into()),
+        }),
+    )
+        .into_response()
+}
+
+/// Looks up an indexed file and makes sure it may be served.
+///
+/// Only files that still resolve to a location inside one of the indexed
+/// roots are returned, so the file endpoints cannot be used to read
+/// arbitrary paths on the host.
+fn resolve_file(state: &AppState, file_id: &str) -> Result<(FileInfo, PathBuf), (StatusCode, String)> {
+    let file_info = match state.indexer.get_file(file_id) {
+        Ok(Some(file_info)) => file_info,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "file not found".to_string())),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+
+    let path = file_info.os_path();
+    match state.indexer.is_within_roots(&crate::archive::on_disk(&path)) {
+        Ok(true) => Ok((file_info, path)),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            "file is not inside an indexed root".to_string(),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Types a browser would run script from. Indexed files are served on the
+/// API's origin, so these are sent as plain text instead.
+const SCRIPTABLE_TYPES: &[&str] = &[
+    "text/html",
+    "application/xhtml+xml",
+    "image/svg+xml",
+    "text/xml",
+    "application/xml",
+];
+
+fn content_type_for(file_info: &FileInfo) -> String {
+    match mime_guess::from_path(&file_info.path).first() {
+        Some(mime) if SCRIPTABLE_TYPES.contains(&mime.essence_str()) => {
+            "text/plain; charset=utf-8".to_string()
+        }
+        Some(mime) if mime.type_() == mime_guess::mime::TEXT => {
+            format!("{}; charset=utf-8", mime.essence_str())
+        }
+        Some(mime) => mime.essence_str().to_string(),
+        None if file_info.content.is_some() => "text/plain; charset=utf-8".to_string(),
+        None => "application/octet-stream".to_string(),
+    }
+}
+
+/// Parses a single-range `Range: bytes=...` header against a file of `len` bytes.
+///
+/// Returns `Ok(None)` when the whole file should be served (no header, or a
+/// multi-range request, which may legally be answered with the full body)
+/// and `Err(())` when the range cannot be satisfied.
+fn parse_range(value: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let spec = match value.trim().strip_prefix("bytes=") {
+        Some(spec) => spec.trim(),
+        None => return Ok(None),
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+
+    let (start, end) = spec.split_once('-').ok_or(())?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return Err(()),
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().map_err(|_| ())?;
+            if suffix == 0 {
+                return Err(());
+            }
+            (len.saturating_sub(suffix), len.checked_sub(1).ok_or(())?)
+        }
+        (start, "") => (start.parse().map_err(|_| ())?, len.checked_sub(1).ok_or(())?),
+        (start, end) => {
+            let start: u64 = start.parse().map_err(|_| ())?;
+            let end: u64 = end.parse().map_err(|_| ())?;
+            (start, end.min(len.saturating_sub(1)))
+        }
+    };
+
+    if start >= len || start > end {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+async fn handler_file(State(state): State<AppState>, Path(file_id): Path<String>) -> Response {
+    let (file_info, _path) = match resolve_file(&state, &file_id) {
+        Ok(resolved) => resolved,
+        Err((status, message)) => return api_error(status, message),
+    };
+
+    let metadata = FileMetadata {
+        content_type: content_type_for(&file_info),
+        has_content: file_info.content.is_some(),
+        truncated: file_info.truncated,
+        file_id: file_info.id,
+        path: file_info.path,
+        size: file_info.size,
+        extension: file_info.extension,
+        created_at: file_info.created_at,
+        modified_at: file_info.modified_at,
+    };
+
+    Json(ApiResponse {
+        success: true,
+        data: Some(metadata),
+        error: None,
+    })
+    .into_response()
+}
+
+async fn handler_file_content(
+    State(state): State<AppState>,
+    Path(file_id): Path<String>,
+    Query(params): Query<ContentParams>,
+    headers: HeaderMap,
+) -> Response {
+    let (file_info, path) = match resolve_file(&state, &file_id) {
+        Ok(resolved) => resolved,
+        Err((status, message)) => return api_error(status, message),
+    };
+
+    // Archive members only exist inside their archive, so they are read
+    // from it whole rather than streamed from disk.
+    let (mut file, member) = match crate::archive::split_member(&path) {
+        Some((archive, name)) => {
+            if file_info.size > MEMBER_LIMIT {
+                return api_error(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("archive members over {} bytes cannot be served; extract {} instead", MEMBER_LIMIT, name),
+                );
+            }
+            let read = tokio::task::spawn_blocking({
+                let archive = archive.clone();
+                let name = name.clone();
+                move || crate::archive::read_member(&archive, &name, MEMBER_LIMIT)
+            });
+            match read.await {
+                Ok(Ok(Some(bytes))) => (None, bytes),
+                Ok(Ok(None)) => {
+                    return api_error(
+                        StatusCode::NOT_FOUND,
+                        format!("{} is no longer in {}", name, archive.display()),
+                    )
+                }
+                Ok(Err(e)) => return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+                Err(e) => return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            }
+        }
+        None => match tokio::fs::File::open(&path).await {
+            Ok(file) => (Some(file), Vec::new()),
+            Err(e) => return api_error(StatusCode::NOT_FOUND, e.to_string()),
+        },
+    };
+    let len = match &file {
+        Some(file) => match file.metadata().await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        None => member.len() as u64,
+    };
+
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => match parse_range(value, len) {
+            Ok(range) => range,
+            Err(()) => {
+                return (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+                )
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+
+    let (status, start, count) = match range {
+        Some((start, end)) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        None => (StatusCode::OK, 0, len),
+    };
+
+    let body = match file.take() {
+        Some(mut file) => {
+            if start > 0 {
+                if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+                    return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+                }
+            }
+            Body::from_stream(ReaderStream::new(file.take(count)))
+        }
+        None => Body::from(member[start as usize..(start + count) as usize].to_vec()),
+    };
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    let disposition = if params.download.unwrap_or(false) {
+        "attachment"
+    } else {
+        "inline"
+    };
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type_for(&file_info))
+        .header(header::CONTENT_LENGTH, count)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_SECURITY_POLICY, "sandbox")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("{}; filename=\"{}\"", disposition, file_name),
+        );
+    if let Some((start, end)) = range {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, len),
+        );
+    }
+
+    response
+        .body(body)
+        .unwrap_or_else(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+async fn handler_file_preview(
+    State(state): State<AppState>,
+    Path(file_id): Path<String>,
+    Query(params): Query<PreviewParams>,
+) -> Response {
+    let (file_info, _path) = match resolve_file(&state, &file_id) {
+        Ok(resolved) => resolved,
+        Err((status, message)) => return api_error(status, message),
+    };
+
+    let title = escape_html(&file_info.path);
+    let content = match &file_info.content {
+        Some(content) => content,
+        None => {
+            return Html(format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+                 <body><h1>{title}</h1><p>No text preview available.</p></body></html>"
+            ))
+            .into_response()
+        }
+    };
+
+    let mut limit = content.len().min(PREVIEW_LIMIT);
+    while !content.is_char_boundary(limit) {
+        limit -= 1;
+    }
+    let shown = &content[..limit];
+
+    let query = params.q.unwrap_or_default();
+    let matches = SearchEngine::new().find_matches(
+        shown,
+        &query,
+        params.case_sensitive.unwrap_or(false),
+    );
+    let mut body = render_highlighted(shown, &matches);
+    if limit < content.len() {
+        body.push_str("\n…");
+    }
+
+    Html(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+         <body><h1>{title}</h1><p>{count} matches</p><pre>{body}</pre></body></html>",
+        count = matches.len(),
+    ))
+    .into_response()
+}
+
+fn render_highlighted(content: &str, matches: &[(usize, usize)]) -> String {
+    let mut html = String::with_capacity(content.len() + matches.len() * 13);
+    let mut last = 0;
+    for &(start, end) in matches {
+        html.push_str(&escape_html(&content[last..start]));
+        html.push_str("<mark>");
+        html.push_str(&escape_html(&content[start..end]));
+        html.push_str("</mark>");
+        last = end;
+    }
+    html.push_str(&escape_html(&content[last..]));
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn test_state(dir: &TempDir) -> AppState {
+        AppState {
+            indexer: Arc::new(Indexer::new(&dir.db()).unwrap()),
+            metrics: Arc::new(QueryMetrics::default()),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-9", 100), Ok(Some((0, 9))));
+        assert_eq!(parse_range("bytes=90-", 100), Ok(Some((90, 99))));
+        assert_eq!(parse_range("bytes=-10", 100), Ok(Some((90, 99))));
+        assert_eq!(parse_range("bytes=50-500", 100), Ok(Some((50, 99))));
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), Ok(None));
+        assert_eq!(parse_range("bytes=100-", 100), Err(()));
+        assert_eq!(parse_range("bytes=9-0", 100), Err(()));
+        assert_eq!(parse_range("bytes=-0", 100), Err(()));
+    }
+
+    #[test]
+    fn test_is_loopback() {
+        assert!(is_loopback("127.0.0.1"));
+        assert!(is_loopback("localhost"));
+        assert!(is_loopback("[::1]"));
+        assert!(!is_loopback("0.0.0.0"));
+        assert!(!is_loopback("192.168.1.10"));
+    }
+
+    #[test]
+    fn test_cors_layer() {
+        assert!(cors_layer(&[]).unwrap().is_none());
+        assert!(cors_layer(&["*".to_string()]).unwrap().is_some());
+        assert!(cors_layer(&["https://search.example.com".to_string()]).unwrap().is_some());
+        assert!(cors_layer(&["bad\norigin".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_request_ids_are_unique() {
+        let mut generator = RequestIdGenerator::default();
+        let request = Request::new(());
+        let first = generator.make_request_id(&request).unwrap();
+        let second = generator.make_request_id(&request).unwrap();
+        assert_ne!(first.header_value(), second.header_value());
+    }
+
+    #[tokio::test]
+    async fn test_live_search_answers_malformed_messages() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let dir = TempDir::new("live");
+        let app = Router::new().route("/api/ws", get(handler_ws)).with_state(test_state(&dir));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/ws", addr)).await.unwrap();
+        let mut reply = async |text: &str| -> serde_json::Value {
+            socket.send(ClientMessage::Text(text.to_string())).await.unwrap();
+            let reply = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+                .await
+                .expect("live search replied")
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(reply.to_text().unwrap()).unwrap()
+        };
+
+        // The very first message has no query to attach the error to.
+        let error = reply("not json").await;
+        assert_eq!(error["success"], false);
+        assert_eq!(error["id"], 0);
+        assert!(error["error"].as_str().unwrap().starts_with("invalid query"));
+
+        let error = reply(r#"{"id": 7, "query": 3}"#).await;
+        assert_eq!(error["id"], 7);
+        assert_eq!(error["success"], false);
+
+        let empty = reply(r#"{"id": 8, "query": ""}"#).await;
+        assert_eq!(empty["id"], 8);
+        assert_eq!(empty["success"], true);
+
+        let relative = reply(r#"{"id": 9, "query": "main", "path_prefix": "src"}"#).await;
+        assert_eq!(relative["id"], 9);
+        assert!(relative["error"].as_str().unwrap().contains("absolute"));
+    }
+
+    #[tokio::test]
+    async fn test_relative_path_prefix_is_rejected() {
+        let dir = TempDir::new("prefix-api");
+        let state = test_state(&dir);
+        let search = |path_prefix: &str| SearchRequest {
+            query: "main".to_string(),
+            all: None,
+            ext: None,
+            limit: None,
+            path_prefix: Some(path_prefix.to_string()),
+        };
+
+        let response = handler_search(State(state.clone()), Json(search("src"))).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = handler_search(State(state.clone()), Json(search(dir.to_str().unwrap()))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let params = DuplicatesParams { path_prefix: Some("../photos".to_string()), min_size: None };
+        let response = handler_duplicates(State(state), Query(params)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_file_content_of_archive_member() {
+        use std::io::Write;
+
+        let dir = TempDir::new("member-api");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        let write_zip = |members: &[(&str, &str)]| {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(root.join("bundle.zip")).unwrap());
+            for (name, content) in members {
+                zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        };
+        write_zip(&[("src/lib.rs", "pub fn zipped() {}"), ("README", "read me")]);
+
+        let settings = IndexSettings { archives: true, ..Default::default() };
+        let state = AppState {
+            indexer: Arc::new(Indexer::with_settings(&dir.db(), settings).unwrap()),
+            metrics: Arc::new(QueryMetrics::default()),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        state.indexer.index_dir(&root).await.unwrap();
+        let hits = state.indexer.search(&crate::testing::content_filter("zipped")).unwrap();
+        assert!(hits[0].path.ends_with("bundle.zip!/src/lib.rs"));
+        let file_id = hits[0].file_id.clone();
+
+        let fetch = |range: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(header::RANGE, range.parse().unwrap());
+            }
+            handler_file_content(
+                State(state.clone()),
+                Path(file_id.clone()),
+                Query(ContentParams { download: None }),
+                headers,
+            )
+        };
+        let body = |response: Response| async {
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+
+        let response = fetch(None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "pub fn zipped() {}");
+        let response = fetch(Some("bytes=4-5")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body(response).await, "fn");
+
+        write_zip(&[("README", "read me")]);
+        let response = fetch(None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(body(response).await.contains("no longer in"));
+    }
+
+    #[tokio::test]
+    async fn test_file_content_of_html_is_not_scriptable() {
+        let dir = TempDir::new("html-api");
+        let root = dir.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("page.html"), "<script>steal()</script>").unwrap();
+        std::fs::write(root.join("icon.svg"), "<svg onload=\"steal()\"/>").unwrap();
+
+        let state = test_state(&dir);
+        state.indexer.index_dir(&root).await.unwrap();
+        for name in ["page.html", "icon.svg"] {
+            let hits = state.indexer.search(&crate::testing::content_filter(name)).unwrap();
+            let response = handler_file_content(
+                State(state.clone()),
+                Path(hits[0].file_id.clone()),
+                Query(ContentParams { download: None }),
+                HeaderMap::new(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let headers = response.headers();
+            assert_eq!(headers[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+            assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+            assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+        }
+    }
+
+    #[test]
+    fn test_render_highlighted_escapes() {
+        let html = render_highlighted("<a> main", &[(4, 8)]);
+        assert_eq!(html, "&lt;a&gt; <mark>main</mark>");
+    }
+}